@@ -1,10 +1,17 @@
 use std::env;
+use std::io::Write;
 
-use string_mult::evaluating::evaluate_list;
+use string_mult::checking::check_list_with;
+use string_mult::evaluating::{evaluate_list_lazy, trace_list, EvalContext, EvalError, Limits};
+use string_mult::formatting::format_list;
+use string_mult::linting::lint_list;
+use string_mult::reporting::{records, write_records, OutputFormat};
+use string_mult::templating::render_with;
 
 /// CLI interface
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let context = context_from_args(&mut args);
 
     if args.len() < 2 || args.contains(&"help".to_string()) {
         println!("Usage: string_mult [OPTIONS]");
@@ -12,6 +19,31 @@ fn main() {
         println!("  help         Print this help message");
         println!("  authors      Print the authors of this program");
         println!("  <path>       Path to a file containing list of string multiplication commands");
+        println!("  --format json|jsonl|csv|tsv <path>");
+        println!("               Print a record with the line, command, status, result or error");
+        println!("               code and message, and span of every command of a file");
+        println!("  --explain <path>");
+        println!("               Print the output of every command after each of its operations");
+        println!(
+            "  check <path> Report commands of a file that fail to parse or evaluate, without"
+        );
+        println!("               printing results; exit with 1 if there are any");
+        println!("  lint <path>  Print warnings about suspicious commands of a file; a warning is");
+        println!("               silenced by a '# allow(W001)' comment following the command");
+        println!("  fmt [--check] <path>");
+        println!("               Rewrite a commands file in canonical style; with --check only");
+        println!("               report whether it is formatted and exit with 1 if it is not");
+        println!("  template <path>");
+        println!(
+            "               Print a file with commands between {{{{ and }}}} replaced by results"
+        );
+        println!("Limits, for evaluating commands of a file:");
+        println!("  --max-output-bytes <n>");
+        println!("               Fail commands whose result is longer than n bytes");
+        println!("  --max-pieces <n>");
+        println!("               Fail commands whose result has more than n pieces");
+        println!("  --max-operations <n>");
+        println!("               Fail commands that apply more than n operations");
         return;
     }
 
@@ -20,13 +52,184 @@ fn main() {
         return;
     }
 
+    if args[1] == "check" {
+        let path = args.get(2).expect("no path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        let report = match check_list_with(&content, &context) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for diagnostic in &report.diagnostics {
+            println!("{}:{}", path, diagnostic);
+        }
+        println!("{}", report);
+        if !report.is_ok() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args[1] == "lint" {
+        let path = args.get(2).expect("no path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        match lint_list(&content) {
+            Ok(warnings) => {
+                for (span, warning) in warnings {
+                    let (line, column) = span.line_col(&content);
+                    println!("{}:{}:{}: {}", path, line, column, warning);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args[1] == "fmt" {
+        let check = args[2..].contains(&"--check".to_string());
+        let path = args[2..]
+            .iter()
+            .find(|a| !a.starts_with("--"))
+            .expect("no path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        let formatted = match format_list(&content) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if check {
+            if formatted != content {
+                println!("{} is not formatted", path);
+                std::process::exit(1);
+            }
+        } else if formatted != content {
+            std::fs::write(path, formatted).expect("could not write file");
+        }
+        return;
+    }
+
+    if args[1] == "template" {
+        let path = args.get(2).expect("no template path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        match render_with(&content, &context) {
+            Ok(s) => print!("{}", s),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.contains(&"--explain".to_string()) {
+        let path = args[1..]
+            .iter()
+            .find(|a| !a.starts_with("--"))
+            .expect("no path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        let traces = match trace_list(&content, &context) {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for (span, trace) in traces {
+            println!("{}", &content[span.start..span.end]);
+            if !matches!(trace.result, Err(EvalError::ParseError(_))) {
+                println!("  start: {}", trace.initial);
+            }
+            for step in &trace.steps {
+                let changed: Vec<String> = step.changed.iter().map(|i| i.to_string()).collect();
+                println!(
+                    "  {}{} -> {}{} ({}changed: {})",
+                    "  ".repeat(step.depth),
+                    step.operation,
+                    step.output,
+                    if step.truncated { "..." } else { "" },
+                    match step.repeat {
+                        1 => String::new(),
+                        repeat => format!("repeated {} times, ", repeat),
+                    },
+                    match changed.is_empty() {
+                        true => "none".to_string(),
+                        false => changed.join(", "),
+                    }
+                );
+            }
+            match trace.result {
+                Ok(s) => println!("  = {}", s),
+                Err(e) => println!("  Error: {}", e),
+            }
+        }
+        return;
+    }
+
+    if let Some(position) = args.iter().position(|a| a == "--format") {
+        let name = args.get(position + 1).expect("no format provided");
+        let Some(format) = OutputFormat::from_name(name) else {
+            eprintln!("Error: unknown format '{}'", name);
+            std::process::exit(1);
+        };
+        let path = args
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(i, a)| *i != position + 1 && !a.starts_with("--"))
+            .map(|(_, a)| a)
+            .expect("no path provided");
+        let content = std::fs::read_to_string(path).expect("could not read file");
+        let records = match records(&content, &context) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let mut out = std::io::stdout().lock();
+        write_records(&mut out, format, &records).expect("could not write output");
+        return;
+    }
+
     let path = &args[1];
     let content = std::fs::read_to_string(path).expect("could not read file");
-    let res = evaluate_list(&content);
+    let res = evaluate_list_lazy(&content, &context);
+    let mut out = std::io::stdout().lock();
     for r in res.unwrap() {
         match r {
-            Ok(s) => println!("{}", s),
-            Err(e) => println!("Error: {}", e),
+            Ok(s) => s.write_to(&mut out).and_then(|_| writeln!(out)),
+            Err(e) => writeln!(out, "Error: {}", e),
         }
+        .expect("could not write output");
+    }
+}
+
+/// Removes the limit options and their values from `args`, returning the context they set.
+fn context_from_args(args: &mut Vec<String>) -> EvalContext {
+    let mut limits = Limits::default();
+    for (option, limit) in [
+        ("--max-output-bytes", &mut limits.max_output_bytes),
+        ("--max-pieces", &mut limits.max_pieces),
+        ("--max-operations", &mut limits.max_operations),
+    ] {
+        if let Some(position) = args.iter().position(|a| a == option) {
+            let Some(value) = args.get(position + 1).and_then(|v| v.parse().ok()) else {
+                eprintln!("Error: {} expects a number", option);
+                std::process::exit(1);
+            };
+            *limit = Some(value);
+            args.drain(position..position + 2);
+        }
+    }
+    EvalContext {
+        limits,
+        ..Default::default()
     }
 }
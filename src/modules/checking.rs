@@ -0,0 +1,93 @@
+//! Provides checking of commands lists without printing their results
+
+use std::fmt;
+
+use super::evaluating::{evaluate_list_spanned, EvalContext, EvalError};
+use super::parsing::ParseError;
+use super::Span;
+
+/// An error of a single command in a commands list.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// Span of the command in the list.
+    pub span: Span,
+    /// Line of the command start, starting from 1.
+    pub line: usize,
+    /// Column of the command start in characters, starting from 1.
+    pub column: usize,
+    /// The error of parsing or evaluating the command.
+    pub error: EvalError,
+}
+
+/// Written on a single line, with line breaks of the error message escaped.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = self
+            .error
+            .to_string()
+            .replace('\r', "\\r")
+            .replace('\n', "\\n");
+        write!(f, "{}:{}: {}", self.line, self.column, message)
+    }
+}
+
+/// Result of checking a commands list.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of checked commands, including wrong ones.
+    pub commands: usize,
+    /// Errors of the commands in order of appearance.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Returns whether every command parsed and evaluated successfully.
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} commands checked, {} failed",
+            self.commands,
+            self.diagnostics.len()
+        )
+    }
+}
+
+/// Parses and evaluates every command of a list, collecting errors instead of results.
+pub fn check_list(input: &str) -> Result<Report, EvalError> {
+    check_list_with(input, &EvalContext::default())
+}
+
+/// Parses and evaluates every command of a list using the provided `EvalContext`, collecting
+/// errors instead of results.
+pub fn check_list_with(input: &str, context: &EvalContext) -> Result<Report, EvalError> {
+    let results = evaluate_list_spanned(input, context)?;
+    let mut report = Report {
+        commands: results.len(),
+        ..Default::default()
+    };
+    for (span, result) in results {
+        if let Err(error) = result {
+            // wrong commands keep the whitespace around them, which the diagnostic leaves out
+            let error = match error {
+                EvalError::ParseError(ParseError::WrongCommand(text)) => {
+                    EvalError::ParseError(ParseError::WrongCommand(text.trim().to_string()))
+                }
+                error => error,
+            };
+            let (line, column) = span.line_col(input);
+            report.diagnostics.push(Diagnostic {
+                span,
+                line,
+                column,
+                error,
+            });
+        }
+    }
+    Ok(report)
+}
@@ -1,14 +1,16 @@
 //! Provides evaluating functionality for `StringMultCommand`
 
-use parsing::{parse_command, ParseError};
-use pest::Parser;
+use parsing::{parse_command, parse_functions, parse_list_spanned, ParseError};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
 use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
 use Either::{Left, Right};
 
-use crate::{Rule, StringMultGrammar};
-
 use super::{
-    parsing, rev_params, to_string, Either, OperationType, ParamsPiece, StringMultCommand,
+    parsing, rev_params, to_string_in, DigitScript, DuplicateOptions, DuplicateStep, Either,
+    Function, OperationType, ParamsPiece, Predicate, ReverseMode, Spanned, StepKind,
+    StringMultCommand, StringMultOperation, TextOperation,
 };
 
 #[derive(Error, Debug)]
@@ -20,138 +22,1017 @@ pub enum EvalError {
     #[error("no command found")]
     /// No command was found
     NoCommand,
-    #[error("parsing error")]
+    #[error("parsing error: {0}")]
     /// Parsing error occured
     ParseError(#[from] ParseError),
 
     #[error("index '{0}' out of range '0..{1}'")]
-    /// Index for multiplication was out of range
+    /// Index of a number an operation applies to was out of range of the numbers
     IndexOutOfRange(usize, usize),
     #[error("duplicating by float is undefined")]
     /// Float argument was provided for multiplication
     DuplicatingByFloat,
+    #[error("number at piece '{0}' is not finite")]
+    /// Operation produced infinity or NaN in the piece at the index
+    NonFiniteResult(usize),
+    #[error("number at piece '{0}' exceeds 2^53 and lost precision")]
+    /// Operation produced a number too large to be represented exactly in the piece at the index
+    PrecisionLoss(usize),
+    #[error("number at piece '{0}' is no longer an integer")]
+    /// Integer number in the piece at the index became non-integral while
+    /// `IntegerPolicy::Reject` was used
+    NonIntegralResult(usize),
+    #[error("modulus is zero")]
+    /// Modulo operation by zero
+    ZeroModulus,
+    #[error("division by zero")]
+    /// Integer division by zero
+    DivisionByZero,
+    #[error("number at piece '{0}' is negative and raised to a fractional power")]
+    /// Fractional power of the negative number in the piece at the index is not a real number
+    FractionalPowerOfNegative(usize),
+    #[error("lower bound '{0}' and upper bound '{1}' do not form a range")]
+    /// Lower bound for clamping was greater than the upper one, or either was NaN
+    InvalidBounds(f64, f64),
+    #[error("padding by float is undefined")]
+    /// Float argument was provided for padding
+    PaddingByFloat,
+    #[error("replaced text is empty")]
+    /// Text to replace was empty
+    EmptyReplacement,
+    #[error("unknown function '{0}'")]
+    /// Called function (provided as name) is not defined
+    UnknownFunction(String),
+    #[error("function '{0}' takes {1} arguments but {2} were given")]
+    /// Function (provided as name) was called with a wrong number of arguments
+    ArgumentCount(String, usize, usize),
+    #[error("function '{0}' calls itself")]
+    /// Function (provided as name) was called during its own evaluation
+    RecursiveFunction(String),
+    #[error("{0} limit of '{1}' exceeded")]
+    /// One of the `Limits` was exceeded
+    LimitExceeded(Limit, usize),
     #[error("unexpected evaluation error")]
     /// Unknown unexpected error
     Unknown,
 }
 
-///Evaluates a list of commands
-pub fn evaluate_list(input: &str) -> Result<Vec<Result<String, EvalError>>, EvalError> {
-    let mut results = Vec::new();
-    let data = StringMultGrammar::parse(Rule::commands_list, input);
-    match data {
-        Ok(mut data) => {
-            let inner = data.next().ok_or(EvalError::NoCommandsList)?.into_inner();
-            for part in inner {
-                results.push(evaluate(part.as_str()));
-            }
-            Ok(results)
+impl EvalError {
+    /// Returns a stable identifier of the kind of the error, e.g. `index_out_of_range`; parsing
+    /// errors keep the code of the `ParseError`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::NoCommandsList => "no_commands_list",
+            EvalError::NoCommand => "no_command",
+            EvalError::ParseError(e) => e.code(),
+            EvalError::IndexOutOfRange(_, _) => "index_out_of_range",
+            EvalError::DuplicatingByFloat => "duplicating_by_float",
+            EvalError::NonFiniteResult(_) => "non_finite_result",
+            EvalError::PrecisionLoss(_) => "precision_loss",
+            EvalError::NonIntegralResult(_) => "non_integral_result",
+            EvalError::ZeroModulus => "zero_modulus",
+            EvalError::DivisionByZero => "division_by_zero",
+            EvalError::FractionalPowerOfNegative(_) => "fractional_power_of_negative",
+            EvalError::InvalidBounds(_, _) => "invalid_bounds",
+            EvalError::PaddingByFloat => "padding_by_float",
+            EvalError::EmptyReplacement => "empty_replacement",
+            EvalError::UnknownFunction(_) => "unknown_function",
+            EvalError::ArgumentCount(_, _, _) => "argument_count",
+            EvalError::RecursiveFunction(_) => "recursive_function",
+            EvalError::LimitExceeded(_, _) => "limit_exceeded",
+            EvalError::Unknown => "unknown",
+        }
+    }
+}
+
+/// Serialized as `{"code": ..., "message": ...}`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for EvalError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("EvalError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// Settings that affect evaluation of commands.
+#[derive(Debug, Clone, Default)]
+pub struct EvalContext {
+    /// How results of operations on integer numbers are handled.
+    pub integer_policy: IntegerPolicy,
+    /// Allow operations to produce infinity or NaN instead of failing with `EvalError::NonFiniteResult`.
+    pub allow_non_finite: bool,
+    /// Allow operations to produce numbers beyond 2^53 instead of failing with `EvalError::PrecisionLoss`.
+    pub allow_precision_loss: bool,
+    /// Resource limits enforced during evaluation.
+    pub limits: Limits,
+    /// How strings are reversed by duplications that do not specify it.
+    pub reverse_mode: ReverseMode,
+    /// Write numbers of the result in the digit script they were written in, instead of ASCII.
+    pub native_digits: bool,
+    /// Functions that commands can call, by name.
+    pub functions: HashMap<String, Function>,
+}
+
+/// Resource limits for a single command; `None` means unlimited.
+///
+/// Limits are checked before duplication allocates, so exceeding them fails with
+/// `EvalError::LimitExceeded` instead of exhausting memory.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Maximum length of the result in bytes.
+    pub max_output_bytes: Option<usize>,
+    /// Maximum number of `ParamsPiece`s in the result.
+    pub max_pieces: Option<usize>,
+    /// Maximum number of operations in a command, including the ones of called functions.
+    pub max_operations: Option<usize>,
+}
+
+/// A kind of limit from `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// `Limits::max_output_bytes`
+    OutputBytes,
+    /// `Limits::max_pieces`
+    Pieces,
+    /// `Limits::max_operations`
+    Operations,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::OutputBytes => write!(f, "output bytes"),
+            Limit::Pieces => write!(f, "pieces"),
+            Limit::Operations => write!(f, "operations"),
         }
-        Err(_) => Err(EvalError::NoCommandsList),
     }
 }
 
+/// The largest magnitude up to which every integer is exactly representable by `f64`.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Policy applied when an operation turns an integer number into a non-integral one.
+///
+/// A number is an integer if it was written without a decimal point, e.g. `12` in `"12 packs"` but
+/// not `12.0`, and no operation made it fractional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerPolicy {
+    /// Keep the non-integral result as is.
+    #[default]
+    Float,
+    /// Round the result to the nearest integer (half away from zero).
+    Round,
+    /// Round the result down.
+    Floor,
+    /// Round the result up.
+    Ceil,
+    /// Drop the fractional part of the result.
+    Truncate,
+    /// Fail with `EvalError::NonIntegralResult`.
+    Reject,
+}
+
+///Evaluates a list of commands
+pub fn evaluate_list(input: &str) -> Result<Vec<Result<String, EvalError>>, EvalError> {
+    evaluate_list_with(input, &EvalContext::default())
+}
+
+///Evaluates a list of commands using the provided `EvalContext`
+pub fn evaluate_list_with(
+    input: &str,
+    context: &EvalContext,
+) -> Result<Vec<Result<String, EvalError>>, EvalError> {
+    let results = evaluate_list_lazy(input, context)?;
+    Ok(results
+        .into_iter()
+        .map(|r| r.map(|output| output.to_string()))
+        .collect())
+}
+
+///Evaluates a list of commands without expanding the final duplications
+///
+///Functions defined in the list are added to the ones of `context`.
+pub fn evaluate_list_lazy(
+    input: &str,
+    context: &EvalContext,
+) -> Result<Vec<Result<LazyOutput, EvalError>>, EvalError> {
+    let results = evaluate_list_spanned(input, context)?;
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+///Evaluates a list of commands like `evaluate_list_lazy`, along with the span of each result in
+///`input`
+pub fn evaluate_list_spanned(
+    input: &str,
+    context: &EvalContext,
+) -> Result<Vec<Spanned<Result<LazyOutput, EvalError>>>, EvalError> {
+    let commands = parse_list_spanned(input).map_err(|_| EvalError::NoCommandsList)?;
+    let context = list_context(input, context)?;
+    Ok(commands
+        .into_iter()
+        .map(|(span, command)| match command {
+            Ok(c) => (span, evaluate_command_lazy(&c, &context)),
+            Err(e) => (span, Err(EvalError::ParseError(e))),
+        })
+        .collect())
+}
+
+///Traces every command of a list like `trace_command`, along with its span in `input`
+///
+///Commands that fail to parse have no steps.
+pub fn trace_list(input: &str, context: &EvalContext) -> Result<Vec<Spanned<Trace>>, EvalError> {
+    let commands = parse_list_spanned(input).map_err(|_| EvalError::NoCommandsList)?;
+    let context = list_context(input, context)?;
+    Ok(commands
+        .into_iter()
+        .map(|(span, command)| match command {
+            Ok(c) => (span, trace_command(&c, &context)),
+            Err(e) => (
+                span,
+                Trace {
+                    initial: String::new(),
+                    steps: Vec::new(),
+                    result: Err(EvalError::ParseError(e)),
+                },
+            ),
+        })
+        .collect())
+}
+
+/// Returns `context` with the functions defined in a list added.
+fn list_context(input: &str, context: &EvalContext) -> Result<EvalContext, EvalError> {
+    let mut context = context.clone();
+    context
+        .functions
+        .extend(parse_functions(input).map_err(|_| EvalError::NoCommandsList)?);
+    Ok(context)
+}
+
 /// Evaluates a single string multiplication command, returning a new String without quote marks.
 pub fn evaluate(input: &str) -> Result<String, EvalError> {
+    evaluate_with(input, &EvalContext::default())
+}
+
+/// Evaluates a single string multiplication command using the provided `EvalContext`.
+pub fn evaluate_with(input: &str, context: &EvalContext) -> Result<String, EvalError> {
+    evaluate_lazy(input, context).map(|output| output.to_string())
+}
+
+/// Evaluates a single string multiplication command without expanding the final duplication.
+pub fn evaluate_lazy(input: &str, context: &EvalContext) -> Result<LazyOutput, EvalError> {
     let comm = parse_command(input);
     match comm {
-        Ok(c) => evaluate_command(&c),
+        Ok(c) => evaluate_command_lazy(&c, context),
         Err(e) => Err(EvalError::ParseError(e)),
     }
 }
 
 /// Evaluates a single `StringMultCommand`, returning a new String without quote marks.
 pub fn evaluate_command(input: &StringMultCommand) -> Result<String, EvalError> {
-    let mut command = input.clone();
+    evaluate_command_with(input, &EvalContext::default())
+}
 
-    for operation in command.operations {
+/// Evaluates a single `StringMultCommand` using the provided `EvalContext`.
+pub fn evaluate_command_with(
+    input: &StringMultCommand,
+    context: &EvalContext,
+) -> Result<String, EvalError> {
+    evaluate_command_lazy(input, context).map(|output| output.to_string())
+}
+
+/// Evaluates a single `StringMultCommand` without expanding the final duplication.
+pub fn evaluate_command_lazy(
+    input: &StringMultCommand,
+    context: &EvalContext,
+) -> Result<LazyOutput, EvalError> {
+    if let Some(max) = context.limits.max_operations {
+        if input.operations.len() > max {
+            return Err(EvalError::LimitExceeded(Limit::Operations, max));
+        }
+    }
+    let mut evaluation = Evaluation::new(input, context);
+    let _ = evaluation.apply_all(&input.operations)?;
+    Ok(evaluation.output)
+}
+
+/// Evaluates a single `StringMultCommand`, recording the output after every operation.
+///
+/// Evaluation stops at the first error, which is kept in `Trace::result` along with the steps
+/// applied before it.
+pub fn trace_command(input: &StringMultCommand, context: &EvalContext) -> Trace {
+    let mut evaluation = Evaluation::new(input, context);
+    let initial = evaluation.output.to_string();
+    evaluation.trace = Some(Vec::new());
+    let result = match context.limits.max_operations {
+        Some(max) if input.operations.len() > max => {
+            Err(EvalError::LimitExceeded(Limit::Operations, max))
+        }
+        _ => evaluation.apply_all(&input.operations).map(|_| ()),
+    };
+    Trace {
+        initial,
+        steps: evaluation.trace.take().unwrap_or_default(),
+        result: result.map(|_| evaluation.output),
+    }
+}
+
+/// Maximum number of bytes of the output recorded in a `TraceStep`.
+pub const MAX_TRACE_OUTPUT_BYTES: usize = 1024;
+
+/// Intermediate outputs of the evaluation of a command.
+#[derive(Debug)]
+pub struct Trace {
+    /// The output before any operation.
+    pub initial: String,
+    /// Applied operations in order; operations of a called function follow the call.
+    pub steps: Vec<TraceStep>,
+    /// The final output, or the error that stopped the evaluation.
+    pub result: Result<LazyOutput, EvalError>,
+}
+
+/// A single operation applied during a traced evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// The applied operation.
+    pub operation: StringMultOperation,
+    /// Number of function calls the operation was applied in, `0` for the command's own.
+    pub depth: usize,
+    /// The output after the operation, cut to at most `MAX_TRACE_OUTPUT_BYTES`.
+    pub output: String,
+    /// Whether `output` was cut.
+    pub truncated: bool,
+    /// How many times the pieces of the output are repeated after the operation.
+    pub repeat: usize,
+    /// Indices of the pieces of a single repetition of the output that differ from the ones
+    /// before the operation.
+    pub changed: Vec<usize>,
+}
+
+/// State of the evaluation of a single command.
+struct Evaluation<'c> {
+    context: &'c EvalContext,
+    output: LazyOutput,
+    /// Names of the functions being called.
+    calls: Vec<String>,
+    /// Number of operations applied so far, including the ones of called functions.
+    applied: usize,
+    /// Steps recorded so far, if the evaluation is traced.
+    trace: Option<Vec<TraceStep>>,
+}
+
+impl<'c> Evaluation<'c> {
+    /// Starts the evaluation of `input`.
+    fn new(input: &StringMultCommand, context: &'c EvalContext) -> Self {
+        Evaluation {
+            context,
+            output: LazyOutput {
+                params: input.params.clone(),
+                digits: match context.native_digits {
+                    true => input.digits,
+                    false => DigitScript::Ascii,
+                },
+                ..Default::default()
+            },
+            calls: Vec::new(),
+            applied: 0,
+            trace: None,
+        }
+    }
+
+    /// Applies `operations` in order, breaking after one that discards the output.
+    fn apply_all(
+        &mut self,
+        operations: &[StringMultOperation],
+    ) -> Result<ControlFlow<()>, EvalError> {
+        for operation in operations {
+            self.applied += 1;
+            if let Some(max) = self.context.limits.max_operations {
+                if self.applied > max {
+                    return Err(EvalError::LimitExceeded(Limit::Operations, max));
+                }
+            }
+            let before = self
+                .trace
+                .as_ref()
+                .map(|trace| (trace.len(), self.output.params.clone()));
+            let flow = self.apply(operation)?;
+            if let Some((position, before)) = before {
+                self.record(position, operation, &before);
+            }
+            if flow.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Inserts the step of a traced operation at `position`, before the steps of the function it
+    /// called.
+    fn record(&mut self, position: usize, operation: &StringMultOperation, before: &[ParamsPiece]) {
+        let changed = self
+            .output
+            .params
+            .iter()
+            .enumerate()
+            .filter(|(i, piece)| before.get(*i) != Some(piece))
+            .map(|(i, _)| i)
+            .collect();
+        let (output, truncated) = self.output.render_prefix(MAX_TRACE_OUTPUT_BYTES);
+        let step = TraceStep {
+            operation: operation.clone(),
+            depth: self.calls.len(),
+            output,
+            truncated,
+            repeat: self.output.repeat,
+            changed,
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.insert(position, step);
+        }
+    }
+
+    /// Applies a single operation to the output.
+    fn apply(&mut self, operation: &StringMultOperation) -> Result<ControlFlow<()>, EvalError> {
+        let limits = &self.context.limits;
         match operation.operation_type {
             OperationType::Mult(index) => {
-                let index = match index {
-                    Some(index) => {
-                        if index < 0 {
-                            (command
-                                .params
-                                .iter()
-                                .filter(|p| matches!(p, ParamsPiece::Num(_)))
-                                .count() as isize
-                                + index) as usize
-                        } else {
-                            index as usize
-                        }
-                    }
-                    None => 0,
+                let argument = match operation.argument {
+                    Left(arg) => arg as f64,
+                    Right(arg) => arg,
                 };
-                let mut i = 0;
+                let index = Some(index.unwrap_or(0));
+                update_numbers(self.context, &mut self.output, index, None, |_, n| {
+                    Ok(n * argument)
+                })?;
+            }
+            OperationType::MultAll => {
                 let argument = match operation.argument {
                     Left(arg) => arg as f64,
                     Right(arg) => arg,
                 };
-                for part in &mut command.params {
-                    match part {
-                        ParamsPiece::Num(n) => {
-                            if i == index {
-                                *part = ParamsPiece::Num(*n * argument);
-                                i = usize::MAX;
-                                break;
-                            }
-                            i += 1;
-                        }
-                        ParamsPiece::Str(_) => continue,
-                    }
-                }
-                if i != usize::MAX {
-                    return Err(EvalError::IndexOutOfRange(
-                        index,
-                        command
-                            .params
-                            .iter()
-                            .filter(|p| matches!(p, ParamsPiece::Num(_)))
-                            .count(),
-                    ));
+                let predicate = operation.predicate.as_ref();
+                update_numbers(self.context, &mut self.output, None, predicate, |_, n| {
+                    Ok(n * argument)
+                })?;
+            }
+            OperationType::Clamp(index, low) => {
+                let high = match operation.argument {
+                    Left(arg) => arg as f64,
+                    Right(arg) => arg,
+                };
+                // `f64::clamp` panics on such bounds
+                if low.is_nan() || high.is_nan() || low > high {
+                    return Err(EvalError::InvalidBounds(low, high));
                 }
+                update_numbers(self.context, &mut self.output, index, None, |_, n| {
+                    Ok(n.clamp(low, high))
+                })?;
             }
-            OperationType::MultAll => {
+            OperationType::Min(index) | OperationType::Max(index) => {
                 let argument = match operation.argument {
                     Left(arg) => arg as f64,
                     Right(arg) => arg,
                 };
-                for part in &mut command.params {
-                    match part {
-                        ParamsPiece::Num(n) => *n *= argument,
-                        ParamsPiece::Str(_) => continue,
+                let is_min = matches!(operation.operation_type, OperationType::Min(_));
+                update_numbers(
+                    self.context,
+                    &mut self.output,
+                    index,
+                    None,
+                    |_, n| match is_min {
+                        true => Ok(n.min(argument)),
+                        false => Ok(n.max(argument)),
+                    },
+                )?;
+            }
+            OperationType::Pow(index) => {
+                let argument = match operation.argument {
+                    Left(arg) => arg as f64,
+                    Right(arg) => arg,
+                };
+                update_numbers(self.context, &mut self.output, index, None, |i, n| {
+                    if argument.fract() == 0.0 && argument.abs() <= i32::MAX as f64 {
+                        Ok(n.powi(argument as i32))
+                    } else if n < 0.0 {
+                        Err(EvalError::FractionalPowerOfNegative(i))
+                    } else {
+                        Ok(n.powf(argument))
                     }
+                })?;
+            }
+            OperationType::Mod(index) | OperationType::IntDiv(index) => {
+                let argument = match operation.argument {
+                    Left(arg) => arg as f64,
+                    Right(arg) => arg,
+                };
+                let is_mod = matches!(operation.operation_type, OperationType::Mod(_));
+                if argument == 0.0 {
+                    return Err(match is_mod {
+                        true => EvalError::ZeroModulus,
+                        false => EvalError::DivisionByZero,
+                    });
                 }
+                update_numbers(
+                    self.context,
+                    &mut self.output,
+                    index,
+                    None,
+                    |_, n| match is_mod {
+                        true => Ok(n.rem_euclid(argument)),
+                        false => Ok(n.div_euclid(argument)),
+                    },
+                )?;
             }
-            OperationType::Duplicate => {
-                let mut argument = match operation.argument {
+            OperationType::Duplicate | OperationType::DuplicateWith(_) => {
+                let argument = match operation.argument {
                     Left(arg) => arg,
                     Right(_) => return Err(EvalError::DuplicatingByFloat),
                 };
                 if argument == 0 {
-                    return Ok(String::new());
+                    self.output = LazyOutput {
+                        digits: self.output.digits,
+                        ..Default::default()
+                    };
+                    return Ok(ControlFlow::Break(()));
+                }
+                let options = match &operation.operation_type {
+                    OperationType::DuplicateWith(options) => options,
+                    _ => &DuplicateOptions::default(),
+                };
+                let separator = options.separator.clone().unwrap_or_default();
+                // a pending repetition keeps its own separator
+                let pending = self.output.repeat > 1
+                    && !self.output.separator.is_empty()
+                    && self.output.separator != separator;
+                if argument < 0 || !separator.is_empty() || !options.steps.is_empty() || pending {
+                    self.output.materialize();
                 }
                 if argument < 0 {
-                    command.params = match rev_params(&command.params) {
+                    let mode = options.reverse.unwrap_or(self.context.reverse_mode);
+                    self.output.params = match rev_params(&self.output.params, mode) {
                         Ok(p) => p,
                         Err(e) => return Err(EvalError::ParseError(e)),
                     };
-                    argument = -argument;
-                }
-                let mut new_parts = Vec::new();
-                for _ in 0..(argument - 1) {
-                    for param in &command.params {
-                        match param {
-                            ParamsPiece::Num(n) => new_parts.push(ParamsPiece::Num(*n)),
-                            ParamsPiece::Str(text) => {
-                                new_parts.push(ParamsPiece::Str(text.to_string()));
+                }
+                let repeat = self.output.repeat.saturating_mul(argument.unsigned_abs());
+                check_duplication(limits, &self.output, &separator, repeat)?;
+                self.output.repeat = repeat;
+                self.output.separator = separator;
+                if !options.steps.is_empty() {
+                    apply_steps(self.context, &mut self.output, &options.steps)?;
+                    // steps can make numbers, and so the output, longer
+                    check_duplication(limits, &self.output, "", 1)?;
+                }
+            }
+            OperationType::Text(ref text) => {
+                apply_text(limits, &mut self.output, text, operation.argument)?;
+            }
+            OperationType::Call(ref name, ref arguments) => {
+                let context = self.context;
+                let function = context
+                    .functions
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnknownFunction(name.clone()))?;
+                if function.params.len() != arguments.len() {
+                    return Err(EvalError::ArgumentCount(
+                        name.clone(),
+                        function.params.len(),
+                        arguments.len(),
+                    ));
+                }
+                if self.calls.contains(name) {
+                    return Err(EvalError::RecursiveFunction(name.clone()));
+                }
+                let operations = bound_operations(function, arguments);
+                self.calls.push(name.clone());
+                let flow = self.apply_all(&operations)?;
+                self.calls.pop();
+                return Ok(flow);
+            }
+        };
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Returns the operations of a function body with the arguments of a call bound to them.
+pub(crate) fn bound_operations(function: &Function, arguments: &[f64]) -> Vec<StringMultOperation> {
+    let mut operations = function.operations.clone();
+    for &(index, param) in &function.bindings {
+        if let (Some(operation), Some(&value)) = (operations.get_mut(index), arguments.get(param)) {
+            operation.argument = bind_argument(operation, value);
+        }
+    }
+    operations
+}
+
+/// Converts a function argument into the argument of the operation bound to it.
+fn bind_argument(operation: &StringMultOperation, value: f64) -> Either<isize, f64> {
+    let integral = matches!(
+        operation.operation_type,
+        OperationType::Duplicate
+            | OperationType::DuplicateWith(_)
+            | OperationType::Text(TextOperation::Pad)
+    );
+    match integral && value.fract() == 0.0 {
+        true => Left(value as isize),
+        false => Right(value),
+    }
+}
+
+/// Result of evaluating a command with the duplication kept unexpanded.
+///
+/// It can be streamed into any `std::io::Write` with `write_to` or into any `std::fmt::Write`
+/// with `write!`, using memory proportional to a single repetition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazyOutput {
+    /// The pieces of a single repetition.
+    pub params: Vec<ParamsPiece>,
+    /// How many times `params` are repeated.
+    pub repeat: usize,
+    /// Text placed between repetitions.
+    pub separator: String,
+    /// The script numbers are written in.
+    pub digits: DigitScript,
+}
+
+impl Default for LazyOutput {
+    fn default() -> Self {
+        LazyOutput {
+            params: Vec::new(),
+            repeat: 1,
+            separator: String::new(),
+            digits: DigitScript::Ascii,
+        }
+    }
+}
+
+impl LazyOutput {
+    /// Writes the result into `writer` one repetition at a time.
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let part = to_string_in(&self.params, self.digits);
+        for i in 0..self.repeat {
+            if i > 0 {
+                writer.write_all(self.separator.as_bytes())?;
+            }
+            writer.write_all(part.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Renders at most `max` bytes of the result, returning whether it was cut.
+    fn render_prefix(&self, max: usize) -> (String, bool) {
+        let part = to_string_in(&self.params, self.digits);
+        let mut result = String::new();
+        for i in 0..self.repeat {
+            if i > 0 {
+                result.push_str(&self.separator);
+            }
+            result.push_str(&part);
+            if result.len() > max {
+                let mut end = max;
+                while !result.is_char_boundary(end) {
+                    end -= 1;
+                }
+                result.truncate(end);
+                return (result, true);
+            }
+        }
+        (result, false)
+    }
+
+    /// Expands the repetitions into `params`.
+    fn materialize(&mut self) {
+        if self.repeat > 1 {
+            let mut params = Vec::new();
+            for i in 0..self.repeat {
+                if i > 0 && !self.separator.is_empty() {
+                    params.push(ParamsPiece::Str(self.separator.clone()));
+                }
+                params.extend(self.params.iter().cloned());
+            }
+            self.params = params;
+        }
+        self.repeat = 1;
+        self.separator = String::new();
+    }
+}
+
+impl std::fmt::Display for LazyOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let part = to_string_in(&self.params, self.digits);
+        for i in 0..self.repeat {
+            if i > 0 {
+                f.write_str(&self.separator)?;
+            }
+            f.write_str(&part)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that duplicating the pieces of `output` the given number of `times`, joined by
+/// `separator`, stays within `limits`.
+fn check_duplication(
+    limits: &Limits,
+    output: &LazyOutput,
+    separator: &str,
+    times: usize,
+) -> Result<(), EvalError> {
+    let params = &output.params;
+    let gaps = if separator.is_empty() { 0 } else { times - 1 };
+    if let Some(max) = limits.max_pieces {
+        match params
+            .len()
+            .checked_mul(times)
+            .and_then(|pieces| pieces.checked_add(gaps))
+        {
+            Some(pieces) if pieces <= max => {}
+            _ => return Err(EvalError::LimitExceeded(Limit::Pieces, max)),
+        }
+    }
+    if let Some(max) = limits.max_output_bytes {
+        match to_string_in(params, output.digits)
+            .len()
+            .checked_mul(times)
+            .zip(separator.len().checked_mul(gaps))
+            .and_then(|(parts, gaps)| parts.checked_add(gaps))
+        {
+            Some(bytes) if bytes <= max => {}
+            _ => return Err(EvalError::LimitExceeded(Limit::OutputBytes, max)),
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the number at `index`, or all numbers matching `predicate` if `index` is `None`,
+/// with the result of `f` called with the index of the piece of the number and the number.
+fn update_numbers(
+    context: &EvalContext,
+    output: &mut LazyOutput,
+    index: Option<isize>,
+    predicate: Option<&Predicate>,
+    f: impl Fn(usize, f64) -> Result<f64, EvalError>,
+) -> Result<(), EvalError> {
+    let selected = match index {
+        Some(index) => {
+            output.materialize();
+            let count = output
+                .params
+                .iter()
+                .filter(|p| matches!(p, ParamsPiece::Num { .. }))
+                .count();
+            Some(resolve_index(index, count)?)
+        }
+        None => None,
+    };
+    let mut i = 0;
+    for (piece, part) in output.params.iter_mut().enumerate() {
+        if let ParamsPiece::Num { value: n, integer } = part {
+            if selected.is_none_or(|s| s == i) && predicate.is_none_or(|p| p.matches(*n)) {
+                *n = checked_result(context, piece, *integer, f(piece, *n)?)?;
+                *integer = *integer && n.fract() == 0.0;
+            }
+            i += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Expands the repetitions of `output`, changing the numbers of each copy by `steps`.
+fn apply_steps(
+    context: &EvalContext,
+    output: &mut LazyOutput,
+    steps: &[DuplicateStep],
+) -> Result<(), EvalError> {
+    let count = output
+        .params
+        .iter()
+        .filter(|p| matches!(p, ParamsPiece::Num { .. }))
+        .count();
+    let mut selected = vec![Vec::new(); count];
+    for step in steps {
+        match step.index {
+            Some(index) => selected[resolve_index(index, count)?].push(step),
+            None => selected.iter_mut().for_each(|s| s.push(step)),
+        }
+    }
+
+    let mut params = Vec::new();
+    for copy in 0..output.repeat {
+        if copy > 0 && !output.separator.is_empty() {
+            params.push(ParamsPiece::Str(output.separator.clone()));
+        }
+        let mut i = 0;
+        for part in &output.params {
+            match part {
+                ParamsPiece::Num { value, integer } => {
+                    let (mut result, mut integer) = (*value, *integer);
+                    for step in &selected[i] {
+                        let changed = match step.kind {
+                            StepKind::Offset => result + step.amount * copy as f64,
+                            StepKind::Scale => result * step.amount.powi(copy as i32),
+                        };
+                        result = checked_result(context, params.len(), integer, changed)?;
+                        integer = integer && result.fract() == 0.0;
+                    }
+                    params.push(ParamsPiece::Num {
+                        value: result,
+                        integer,
+                    });
+                    i += 1;
+                }
+                ParamsPiece::Str(_) => params.push(part.clone()),
+            }
+        }
+    }
+    output.params = params;
+    output.repeat = 1;
+    output.separator = String::new();
+    Ok(())
+}
+
+/// Applies the text operation to the text pieces and the separator of `output`.
+fn apply_text(
+    limits: &Limits,
+    output: &mut LazyOutput,
+    operation: &TextOperation,
+    argument: Either<isize, f64>,
+) -> Result<(), EvalError> {
+    let map_text = |output: &mut LazyOutput, f: &dyn Fn(&str) -> String| {
+        for part in &mut output.params {
+            if let ParamsPiece::Str(text) = part {
+                *text = f(text);
+            }
+        }
+        output.separator = f(&output.separator);
+    };
+    // changing case or replacing text can make the output longer
+    let check_len = |output: &LazyOutput, mapped: &dyn Fn(&str) -> Option<usize>| match limits
+        .max_output_bytes
+    {
+        Some(max) if !matches!(mapped_len(output, mapped), Some(bytes) if bytes <= max) => {
+            Err(EvalError::LimitExceeded(Limit::OutputBytes, max))
+        }
+        _ => Ok(()),
+    };
+    match operation {
+        TextOperation::Upper => {
+            check_len(output, &|text| {
+                Some(
+                    text.chars()
+                        .flat_map(char::to_uppercase)
+                        .map(char::len_utf8)
+                        .sum(),
+                )
+            })?;
+            map_text(output, &|text| text.to_uppercase())
+        }
+        TextOperation::Lower => {
+            check_len(output, &|text| {
+                Some(
+                    text.chars()
+                        .flat_map(char::to_lowercase)
+                        .map(char::len_utf8)
+                        .sum(),
+                )
+            })?;
+            map_text(output, &|text| text.to_lowercase())
+        }
+        TextOperation::Replace(from, _) if from.is_empty() => {
+            return Err(EvalError::EmptyReplacement)
+        }
+        TextOperation::Replace(from, to) => {
+            check_len(output, &|text| {
+                let count = text.matches(from.as_str()).count();
+                (text.len() - count * from.len()).checked_add(count.checked_mul(to.len())?)
+            })?;
+            map_text(output, &|text| text.replace(from, to))
+        }
+        TextOperation::Title => {
+            output.materialize();
+            let mut word_start = true;
+            for part in &mut output.params {
+                match part {
+                    ParamsPiece::Num { .. } => word_start = false,
+                    ParamsPiece::Str(text) => {
+                        let mut titled = String::with_capacity(text.len());
+                        for c in text.chars() {
+                            if word_start {
+                                titled.extend(c.to_uppercase());
+                            } else {
+                                titled.extend(c.to_lowercase());
                             }
+                            word_start = !c.is_alphanumeric();
                         }
+                        *text = titled;
                     }
                 }
-                command.params.extend(new_parts);
             }
+            check_len(output, &|text| Some(text.len()))?;
+        }
+        TextOperation::Trim => {
+            output.materialize();
+            while let Some(ParamsPiece::Str(text)) = output.params.first_mut() {
+                *text = text.trim_start().to_string();
+                if !text.is_empty() {
+                    break;
+                }
+                output.params.remove(0);
+            }
+            while let Some(ParamsPiece::Str(text)) = output.params.last_mut() {
+                *text = text.trim_end().to_string();
+                if !text.is_empty() {
+                    break;
+                }
+                output.params.pop();
+            }
+        }
+        TextOperation::Pad => {
+            let width = match argument {
+                Left(arg) => arg,
+                Right(_) => return Err(EvalError::PaddingByFloat),
+            };
+            if let Some(max) = limits.max_output_bytes {
+                if width.unsigned_abs() > max {
+                    return Err(EvalError::LimitExceeded(Limit::OutputBytes, max));
+                }
+            }
+            let length = output.to_string().graphemes(true).count();
+            let padding = " ".repeat(width.unsigned_abs().saturating_sub(length));
+            if padding.is_empty() {
+                return Ok(());
+            }
+            output.materialize();
+            if width > 0 {
+                output.params.push(ParamsPiece::Str(padding));
+            } else {
+                output.params.insert(0, ParamsPiece::Str(padding));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the length in bytes of `output` after mapping each of its texts to a text of the
+/// length given by `mapped`, or `None` if it overflows.
+fn mapped_len(output: &LazyOutput, mapped: &dyn Fn(&str) -> Option<usize>) -> Option<usize> {
+    let mut part = to_string_in(&output.params, output.digits).len();
+    for piece in &output.params {
+        if let ParamsPiece::Str(text) = piece {
+            part = (part - text.len()).checked_add(mapped(text)?)?;
+        }
+    }
+    let gaps = mapped(&output.separator)?.checked_mul(output.repeat.saturating_sub(1))?;
+    part.checked_mul(output.repeat)?.checked_add(gaps)
+}
+
+/// Resolves a possibly negative `index` of a number among `count` numbers.
+fn resolve_index(index: isize, count: usize) -> Result<usize, EvalError> {
+    let resolved = if index < 0 {
+        count.checked_sub(index.unsigned_abs())
+    } else {
+        Some(index as usize)
+    };
+    match resolved {
+        Some(resolved) if resolved < count => Ok(resolved),
+        _ => Err(EvalError::IndexOutOfRange(
+            resolved.unwrap_or(index.unsigned_abs()),
+            count,
+        )),
+    }
+}
+
+/// Validates the `result` of an operation on the number in the piece at `index` against the
+/// `context`;
+/// `integer` tells whether the number was an integer before the operation.
+fn checked_result(
+    context: &EvalContext,
+    index: usize,
+    integer: bool,
+    result: f64,
+) -> Result<f64, EvalError> {
+    if !result.is_finite() {
+        return if context.allow_non_finite {
+            Ok(result)
+        } else {
+            Err(EvalError::NonFiniteResult(index))
         };
     }
-    Ok(to_string(&command.params))
+    if result.abs() > MAX_EXACT_INTEGER && !context.allow_precision_loss {
+        return Err(EvalError::PrecisionLoss(index));
+    }
+    if !integer || result.fract() == 0.0 {
+        return Ok(result);
+    }
+    match context.integer_policy {
+        IntegerPolicy::Float => Ok(result),
+        IntegerPolicy::Round => Ok(result.round()),
+        IntegerPolicy::Floor => Ok(result.floor()),
+        IntegerPolicy::Ceil => Ok(result.ceil()),
+        IntegerPolicy::Truncate => Ok(result.trunc()),
+        IntegerPolicy::Reject => Err(EvalError::NonIntegralResult(index)),
+    }
 }
@@ -0,0 +1,171 @@
+//! Provides formatting of commands lists into canonical style
+
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
+
+use super::parsing::{parse_operation_pairs, parse_params, ParseError};
+use super::printing::{print, print_bound_operation, PrintOptions};
+use super::{DigitScript, StringMultCommand};
+use crate::{Rule, StringMultGrammar};
+
+/// Formats a list of commands into canonical style.
+///
+/// Commands and definitions are written as `printing::print` writes them, with macro calls kept
+/// as `@name`, and lines lose their indentation and trailing whitespace. Line breaks, comments
+/// and wrong commands are kept verbatim, except for the whitespace after wrong commands.
+pub fn format_list(input: &str) -> Result<String, ParseError> {
+    let list = match StringMultGrammar::parse(Rule::commands_list, input) {
+        Ok(mut data) => data.next().ok_or(ParseError::NoCommandsList)?,
+        Err(_) => return Err(ParseError::NoCommandsList),
+    };
+    let mut result = String::with_capacity(input.len());
+    let mut end = 0;
+    for part in list.into_inner() {
+        let span = part.as_span();
+        let part_rule = part.as_rule();
+        let verbatim = matches!(part_rule, Rule::wrong_command | Rule::comment);
+        // trailing whitespace of wrong commands is left to the gap before the next item
+        let text = match part_rule {
+            Rule::wrong_command => part.as_str().trim_end().to_string(),
+            Rule::comment => part.as_str().to_string(),
+            _ => format_pair(part),
+        };
+        // commands include their indentation, which is dropped
+        let indentation = span.as_str().len() - span.as_str().trim_start().len();
+        let start = span.start() + if verbatim { 0 } else { indentation };
+        let gap = &input[end..start];
+        match line_breaks(gap) {
+            breaks if !breaks.is_empty() => result.push_str(&breaks),
+            _ if result.is_empty() || result.ends_with('\n') => {}
+            _ => result.push(' '),
+        }
+        // wrong commands keep their indentation as well
+        if let (Rule::wrong_command, Some(line_start)) = (part_rule, gap.rfind('\n')) {
+            result.push_str(&gap[line_start + 1..]);
+        }
+        end = match part_rule {
+            Rule::wrong_command => span.start() + text.len(),
+            _ => span.end(),
+        };
+        result.push_str(&text);
+    }
+    result.push_str(&line_breaks(&input[end..]));
+    Ok(result)
+}
+
+/// Returns whether a list of commands is already in canonical style.
+pub fn is_formatted(input: &str) -> Result<bool, ParseError> {
+    Ok(format_list(input)? == input)
+}
+
+/// Keeps only the line breaks of whitespace between items of a list.
+fn line_breaks(gap: &str) -> String {
+    let mut result = String::new();
+    let mut carriage_return = false;
+    for c in gap.chars() {
+        match c {
+            '\n' if carriage_return => result.push_str("\r\n"),
+            '\n' => result.push('\n'),
+            _ => {}
+        }
+        carriage_return = c == '\r';
+    }
+    result
+}
+
+/// Formats a command or a definition as `printing::print` prints it.
+fn format_pair(pair: Pair<'_, Rule>) -> String {
+    let rule = pair.as_rule();
+    let text = pair.as_str();
+    let mut inner = pair.into_inner();
+    match rule {
+        Rule::command => {
+            let params = inner.next().map(format_params).unwrap_or_default();
+            format!("{params} {}", format_operations(inner, &[]))
+        }
+        Rule::macro_def => match (inner.next(), inner.next()) {
+            (Some(name), Some(body)) => format!(
+                "macro {} = {}",
+                name.as_str(),
+                format_operations(body.into_inner(), &[])
+            ),
+            _ => text.to_string(),
+        },
+        Rule::fn_def => {
+            let mut idents = Vec::new();
+            let mut body = String::new();
+            for part in inner {
+                match part.as_rule() {
+                    Rule::ident => idents.push(part.as_str()),
+                    _ => {
+                        body = format_operations(part.into_inner(), &idents[1.min(idents.len())..])
+                    }
+                }
+            }
+            match idents.split_first() {
+                Some((name, params)) => format!("fn {name}({}) = {body}", params.join(", ")),
+                None => text.to_string(),
+            }
+        }
+        _ => text.to_string(),
+    }
+}
+
+/// Formats a `str_param` pair as the string parameter of a printed command.
+fn format_params(param: Pair<'_, Rule>) -> String {
+    let text = param.as_str();
+    match parse_params(text) {
+        Ok(params) => print(&StringMultCommand {
+            params,
+            operations: Vec::new(),
+            digits: DigitScript::detect(text),
+        }),
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Formats operation pairs as printed operations separated by single spaces; macro calls are
+/// kept, and the names of `params` are written in place of the arguments bound to them.
+fn format_operations<'i>(pairs: Pairs<'i, Rule>, params: &[&'i str]) -> String {
+    // an operation starts with its operator, followed by its argument and options
+    let mut segments: Vec<Vec<Pair<'i, Rule>>> = Vec::new();
+    for pair in pairs {
+        let option = matches!(
+            pair.as_rule(),
+            Rule::int
+                | Rule::num
+                | Rule::param_ref
+                | Rule::separator
+                | Rule::step
+                | Rule::reverse
+                | Rule::filter
+        );
+        match segments.last_mut() {
+            Some(segment) if option => segment.push(pair),
+            _ => segments.push(vec![pair]),
+        }
+    }
+    segments
+        .into_iter()
+        .map(|segment| format_operation(segment, params))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Formats the pairs of a single operation; tokens are kept as written if they do not parse.
+fn format_operation<'i>(segment: Vec<Pair<'i, Rule>>, params: &[&'i str]) -> String {
+    let written: Vec<&str> = segment.iter().map(|pair| pair.as_str()).collect();
+    let written = written.join(" ");
+    if segment.first().map(|pair| pair.as_rule()) == Some(Rule::macro_call) {
+        return written;
+    }
+    match parse_operation_pairs(segment, params) {
+        Ok(function) if function.operations.len() == 1 => {
+            let param = function.bindings.first().map(|(_, param)| params[*param]);
+            print_bound_operation(&function.operations[0], &PrintOptions::default(), param)
+        }
+        _ => written,
+    }
+}
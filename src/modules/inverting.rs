@@ -0,0 +1,233 @@
+//! Provides undoing of `StringMultCommand` operations on their output
+
+use std::fmt;
+
+use thiserror::Error;
+
+use super::evaluating::{bound_operations, evaluate_command_with, EvalContext, EvalError};
+use super::parsing::{parse_params, ParseError};
+use super::{
+    escape, DigitScript, DuplicateOptions, Either, OperationType, StringMultCommand,
+    StringMultOperation,
+};
+
+#[derive(Error, Debug)]
+/// An error that can occur during inversion of a command or undoing of its output.
+pub enum InvertError {
+    #[error("parsing error: {0}")]
+    /// Text being undone could not be parsed
+    ParseError(#[from] ParseError),
+    #[error("evaluation error: {0}")]
+    /// The command or a step undoing it failed to evaluate
+    EvalError(#[from] EvalError),
+    #[error("operation at index '{0}' discards the output")]
+    /// Duplication by 0 leaves nothing to undo
+    DiscardedOutput(usize),
+    #[error("operation at index '{0}' multiplies by 0, losing the numbers")]
+    /// Multiplication by 0 has no reciprocal
+    MultiplicationByZero(usize),
+    #[error("operation at index '{0}' gives the same result for different strings")]
+    /// Clamping, rounding, modulo and text operations lose the original values
+    LossyOperation(usize),
+    #[error("operation at index '{0}' only applies to numbers matching its condition")]
+    /// Numbers the predicate applied to cannot be told apart afterwards
+    ConditionalOperation(usize),
+    #[error("operation at index '{0}' makes repetitions differ from each other")]
+    /// Duplication with steps does not produce exact repetitions
+    UnequalRepetitions(usize),
+    #[error("output is not made of '{0}' equal repetitions")]
+    /// Undone text is not the repetition it is expected to be
+    NotRepeated(usize),
+    #[error("undoing the command gives '{0}' instead of its string")]
+    /// Steps do not restore the string of the command, e.g. due to rounding
+    NotRestored(String),
+}
+
+/// A step undoing an operation of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UndoStep {
+    /// Apply an operation to the text.
+    Operation(StringMultOperation),
+    /// Keep the first of this many equal repetitions joined by the separator.
+    Deduplicate(usize, String),
+}
+
+impl fmt::Display for UndoStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UndoStep::Operation(operation) => write!(f, "{operation}"),
+            UndoStep::Deduplicate(times, separator) if separator.is_empty() => {
+                write!(f, "dedup {times}")
+            }
+            UndoStep::Deduplicate(times, separator) => {
+                write!(f, "dedup {times} sep \"{}\"", escape(separator))
+            }
+        }
+    }
+}
+
+/// Returns the steps turning the output of a command evaluated with the default `EvalContext`
+/// back into its string.
+///
+/// See `invert_with`.
+pub fn invert(command: &StringMultCommand) -> Result<Vec<UndoStep>, InvertError> {
+    invert_with(command, &EvalContext::default())
+}
+
+/// Returns the steps turning the output of a command evaluated with `context` back into its
+/// string.
+///
+/// Multiplications and powers are undone with reciprocal arguments, duplications by keeping
+/// the first repetition, and negative duplications by reversing it again. The steps are checked
+/// to restore the string of the command, so that rounding or numbers merging with their
+/// neighbours are reported as `InvertError::NotRestored`.
+pub fn invert_with(
+    command: &StringMultCommand,
+    context: &EvalContext,
+) -> Result<Vec<UndoStep>, InvertError> {
+    let output = evaluate_command_with(command, context)?;
+    let mut inverses = Vec::new();
+    for (i, operation) in command.operations.iter().enumerate() {
+        invert_operation(i, operation, context, &mut Vec::new(), &mut inverses)?;
+    }
+    let steps: Vec<UndoStep> = inverses.into_iter().rev().flatten().collect();
+    let original = StringMultCommand {
+        operations: Vec::new(),
+        ..command.clone()
+    };
+    let original = evaluate_command_with(&original, context)?;
+    match undo_with(&output, &steps, context)? {
+        restored if restored == original => Ok(steps),
+        restored => Err(InvertError::NotRestored(restored)),
+    }
+}
+
+/// Collects the steps undoing an operation of a command at index `i`; operations of called
+/// functions are inverted one by one.
+fn invert_operation(
+    i: usize,
+    operation: &StringMultOperation,
+    context: &EvalContext,
+    calls: &mut Vec<String>,
+    inverses: &mut Vec<Vec<UndoStep>>,
+) -> Result<(), InvertError> {
+    if operation.predicate.is_some() {
+        return Err(InvertError::ConditionalOperation(i));
+    }
+    let argument = match operation.argument {
+        Either::Left(arg) => arg as f64,
+        Either::Right(arg) => arg,
+    };
+    let reciprocal = |operation_type: &OperationType| {
+        vec![UndoStep::Operation(StringMultOperation {
+            operation_type: operation_type.clone(),
+            argument: Either::Right(1.0 / argument),
+            predicate: None,
+        })]
+    };
+    let steps = match &operation.operation_type {
+        OperationType::Mult(_) | OperationType::MultAll if argument == 0.0 => {
+            return Err(InvertError::MultiplicationByZero(i))
+        }
+        OperationType::Mult(_) | OperationType::MultAll => reciprocal(&operation.operation_type),
+        OperationType::Pow(_) if argument == 0.0 => return Err(InvertError::LossyOperation(i)),
+        OperationType::Pow(_) => reciprocal(&operation.operation_type),
+        OperationType::Clamp(_, _)
+        | OperationType::Min(_)
+        | OperationType::Max(_)
+        | OperationType::Mod(_)
+        | OperationType::IntDiv(_)
+        | OperationType::Text(_) => return Err(InvertError::LossyOperation(i)),
+        OperationType::Duplicate | OperationType::DuplicateWith(_) => {
+            let times = match operation.argument {
+                Either::Left(0) => return Err(InvertError::DiscardedOutput(i)),
+                Either::Left(times) => times,
+                Either::Right(_) => return Err(EvalError::DuplicatingByFloat.into()),
+            };
+            let options = match &operation.operation_type {
+                OperationType::DuplicateWith(options) => options.clone(),
+                _ => DuplicateOptions::default(),
+            };
+            if !options.steps.is_empty() {
+                return Err(InvertError::UnequalRepetitions(i));
+            }
+            let mut steps = Vec::new();
+            if times.unsigned_abs() > 1 {
+                let separator = options.separator.unwrap_or_default();
+                steps.push(UndoStep::Deduplicate(times.unsigned_abs(), separator));
+            }
+            if times < 0 {
+                steps.push(UndoStep::Operation(StringMultOperation {
+                    operation_type: OperationType::DuplicateWith(DuplicateOptions {
+                        reverse: Some(options.reverse.unwrap_or(context.reverse_mode)),
+                        ..Default::default()
+                    }),
+                    argument: Either::Left(-1),
+                    predicate: None,
+                }));
+            }
+            steps
+        }
+        OperationType::Call(name, arguments) => {
+            let function = context
+                .functions
+                .get(name)
+                .ok_or_else(|| EvalError::UnknownFunction(name.clone()))?;
+            if calls.contains(name) {
+                return Err(EvalError::RecursiveFunction(name.clone()).into());
+            }
+            calls.push(name.clone());
+            for operation in bound_operations(function, arguments) {
+                invert_operation(i, &operation, context, calls, inverses)?;
+            }
+            calls.pop();
+            return Ok(());
+        }
+    };
+    inverses.push(steps);
+    Ok(())
+}
+
+/// Applies undo steps to the output of a command evaluated with the default `EvalContext`.
+pub fn undo(output: &str, steps: &[UndoStep]) -> Result<String, InvertError> {
+    undo_with(output, steps, &EvalContext::default())
+}
+
+/// Applies undo steps to the output of a command, evaluating operations with `context`.
+///
+/// The text is parsed again before every operation, like a string parameter.
+pub fn undo_with(
+    output: &str,
+    steps: &[UndoStep],
+    context: &EvalContext,
+) -> Result<String, InvertError> {
+    let mut text = output.to_string();
+    for step in steps {
+        text = match step {
+            UndoStep::Operation(operation) => {
+                let command = StringMultCommand {
+                    params: parse_params(&format!("\"{}\"", escape(&text)))?,
+                    operations: vec![operation.clone()],
+                    digits: DigitScript::detect(&text),
+                };
+                evaluate_command_with(&command, context)?
+            }
+            UndoStep::Deduplicate(times, separator) => deduplicate(&text, *times, separator)
+                .ok_or(InvertError::NotRepeated(*times))?
+                .to_string(),
+        };
+    }
+    Ok(text)
+}
+
+/// Returns the first of `times` equal parts of `text` joined by `separator`.
+fn deduplicate<'t>(text: &'t str, times: usize, separator: &str) -> Option<&'t str> {
+    // `times` parts and `times - 1` separators make up the text
+    let total = text.len() + separator.len();
+    if !total.is_multiple_of(times) {
+        return None;
+    }
+    let length = (total / times).checked_sub(separator.len())?;
+    let part = text.get(..length)?;
+    (vec![part; times].join(separator) == text).then_some(part)
+}
@@ -0,0 +1,222 @@
+//! Provides warnings about commands that are valid but likely mistaken
+
+use std::collections::HashMap;
+use std::fmt;
+
+use pest::Parser;
+
+use super::parsing::{parse_list_spanned, ParseError};
+use super::{Either, OperationType, ParamsPiece, ReverseMode, Spanned, StringMultCommand};
+use crate::{Rule, StringMultGrammar};
+
+/// A kind of suspicious operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// `W001`: multiplication or power by 1, e.g. `** 1`.
+    NoOpMultiplication,
+    /// `W002`: duplication by 1, e.g. `*** 1`.
+    NoOpDuplication,
+    /// `W003`: index of a number that the string does not have, e.g. `*[0]` on `"abc"`.
+    IndexOutOfRange,
+    /// `W004`: multiplication by 0, e.g. `** 0`.
+    MultiplicationByZero,
+    /// `W005`: operations after `*** 0`, which are never applied.
+    IgnoredOperations,
+}
+
+impl Lint {
+    /// All lints in the order of their codes.
+    pub const ALL: [Lint; 5] = [
+        Lint::NoOpMultiplication,
+        Lint::NoOpDuplication,
+        Lint::IndexOutOfRange,
+        Lint::MultiplicationByZero,
+        Lint::IgnoredOperations,
+    ];
+
+    /// Returns the code of the lint, e.g. `W001`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::NoOpMultiplication => "W001",
+            Lint::NoOpDuplication => "W002",
+            Lint::IndexOutOfRange => "W003",
+            Lint::MultiplicationByZero => "W004",
+            Lint::IgnoredOperations => "W005",
+        }
+    }
+
+    /// Returns the lint with the given code.
+    pub fn from_code(code: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.code() == code)
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::NoOpMultiplication => write!(f, "multiplication by 1 has no effect"),
+            Lint::NoOpDuplication => write!(f, "duplication by 1 has no effect"),
+            Lint::IndexOutOfRange => write!(f, "index is out of range of the string numbers"),
+            Lint::MultiplicationByZero => write!(f, "multiplication by 0 discards the number"),
+            Lint::IgnoredOperations => write!(f, "operations after duplication by 0 are ignored"),
+        }
+    }
+}
+
+/// A warning about an operation of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// The kind of the warning.
+    pub lint: Lint,
+    /// Index of the operation in `StringMultCommand::operations`.
+    pub operation: usize,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in operation {}: {}",
+            self.lint.code(),
+            self.operation + 1,
+            self.lint
+        )
+    }
+}
+
+/// Returns warnings about suspicious operations of a command.
+pub fn lint_command(command: &StringMultCommand) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut warn = |lint, operation| warnings.push(Warning { lint, operation });
+    // number of numbers in the string, if it is known before evaluation
+    let mut numbers = Some(
+        command
+            .params
+            .iter()
+            .filter(|p| matches!(p, ParamsPiece::Num { .. }))
+            .count(),
+    );
+    for (i, operation) in command.operations.iter().enumerate() {
+        let argument = match operation.argument {
+            Either::Left(arg) => arg as f64,
+            Either::Right(arg) => arg,
+        };
+        let index = match operation.operation_type {
+            OperationType::Mult(index) => Some(index.unwrap_or(0)),
+            OperationType::Clamp(index, _)
+            | OperationType::Min(index)
+            | OperationType::Max(index)
+            | OperationType::Pow(index)
+            | OperationType::Mod(index)
+            | OperationType::IntDiv(index) => index,
+            _ => None,
+        };
+        if let (Some(index), Some(count)) = (index, numbers) {
+            let in_range = match index < 0 {
+                true => index.unsigned_abs() <= count,
+                false => (index as usize) < count,
+            };
+            if !in_range {
+                warn(Lint::IndexOutOfRange, i);
+            }
+        }
+        match &operation.operation_type {
+            OperationType::Mult(_) | OperationType::MultAll | OperationType::Pow(_)
+                if argument == 1.0 =>
+            {
+                warn(Lint::NoOpMultiplication, i)
+            }
+            OperationType::Mult(_) | OperationType::MultAll if argument == 0.0 => {
+                warn(Lint::MultiplicationByZero, i)
+            }
+            OperationType::Duplicate | OperationType::DuplicateWith(_) => {
+                let reverse = match &operation.operation_type {
+                    OperationType::DuplicateWith(options) => options.reverse,
+                    _ => None,
+                };
+                match operation.argument {
+                    Either::Left(1) => warn(Lint::NoOpDuplication, i),
+                    Either::Left(0) if i + 1 < command.operations.len() => {
+                        warn(Lint::IgnoredOperations, i);
+                        break;
+                    }
+                    // reversed text is parsed again, so numbers may change
+                    Either::Left(times) if times < 0 && reverse != Some(ReverseMode::Pieces) => {
+                        numbers = None
+                    }
+                    Either::Left(times) => {
+                        numbers = numbers.map(|n| n.saturating_mul(times.unsigned_abs()))
+                    }
+                    Either::Right(_) => numbers = None,
+                }
+            }
+            OperationType::Call(_, _) => numbers = None,
+            _ => {}
+        }
+    }
+    warnings
+}
+
+/// Returns warnings about the commands of a list along with their spans.
+///
+/// Warnings listed in a `# allow(W001, ...)` comment right after the command, on its line, are
+/// left out. Commands that fail to parse produce no warnings.
+pub fn lint_list(input: &str) -> Result<Vec<Spanned<Warning>>, ParseError> {
+    let mut result = Vec::new();
+    let allowed = allowed_lints(input);
+    for (span, command) in parse_list_spanned(input)? {
+        let Ok(command) = command else {
+            continue;
+        };
+        let allowed = allowed
+            .get(&span.end)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for warning in lint_command(&command) {
+            if !allowed.contains(&warning.lint) {
+                result.push((span, warning));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Retrieves lints allowed by `# allow(...)` comments of a list, by the end of the command each
+/// comment follows on the same line.
+fn allowed_lints(input: &str) -> HashMap<usize, Vec<Lint>> {
+    let mut allowed = HashMap::new();
+    let Some(list) = StringMultGrammar::parse(Rule::commands_list, input)
+        .ok()
+        .and_then(|mut data| data.next())
+    else {
+        return allowed;
+    };
+    let mut command_end = None;
+    for part in list.into_inner() {
+        let span = part.as_span();
+        match (part.as_rule(), command_end) {
+            (Rule::command, _) => command_end = Some(span.end()),
+            (Rule::comment, Some(end)) if !input[end..span.start()].contains('\n') => {
+                allowed.insert(end, comment_lints(part.as_str()));
+                command_end = None;
+            }
+            _ => command_end = None,
+        }
+    }
+    allowed
+}
+
+/// Retrieves lints listed in an `# allow(...)` comment.
+fn comment_lints(comment: &str) -> Vec<Lint> {
+    let codes = comment
+        .trim_start_matches('#')
+        .trim()
+        .strip_prefix("allow(")
+        .and_then(|c| c.split_once(')'))
+        .map(|(codes, _)| codes)
+        .unwrap_or_default();
+    codes
+        .split(',')
+        .filter_map(|code| Lint::from_code(code.trim()))
+        .collect()
+}
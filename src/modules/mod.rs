@@ -1,65 +1,507 @@
+pub mod checking;
 pub mod evaluating;
+pub mod formatting;
+pub mod inverting;
+pub mod linting;
+pub mod optimizing;
 pub mod parsing;
+pub mod printing;
+pub mod reporting;
+pub mod solving;
+pub mod templating;
 
 use either::Either;
 use parsing::ParseError;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// A single string multiplication command.
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringMultCommand {
     /// The string to operate on.
     pub params: Vec<ParamsPiece>,
     /// The operations to perform.
     pub operations: Vec<StringMultOperation>,
+    /// The script of the digits the numbers were written in.
+    pub digits: DigitScript,
+}
+
+/// A range of byte offsets in the parsed input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    /// Offset of the first byte.
+    pub start: usize,
+    /// Offset after the last byte.
+    pub end: usize,
+}
+
+/// A value along with its span in the parsed input.
+pub type Spanned<T> = (Span, T);
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+impl Span {
+    /// Returns the line and the column in characters of the start in `input`, both starting
+    /// from 1.
+    pub fn line_col(&self, input: &str) -> (usize, usize) {
+        let before = &input[..self.start.min(input.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+        (line, column)
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
 }
 
 /// Reverses the parameters.
-pub(crate) fn rev_params(params: &[ParamsPiece]) -> Result<Vec<ParamsPiece>, ParseError> {
+pub(crate) fn rev_params(
+    params: &[ParamsPiece],
+    mode: ReverseMode,
+) -> Result<Vec<ParamsPiece>, ParseError> {
     let str = to_string(params);
-    let new_str = format!("\"{}\"", str.chars().rev().collect::<String>());
-    parsing::parse_params(&new_str)
+    let reversed = match mode {
+        ReverseMode::Chars => str.chars().rev().collect::<String>(),
+        ReverseMode::Graphemes => rev_graphemes(&str),
+        ReverseMode::Pieces => {
+            return Ok(params
+                .iter()
+                .rev()
+                .map(|p| match p {
+                    ParamsPiece::Num { .. } => p.clone(),
+                    ParamsPiece::Str(text) => ParamsPiece::Str(rev_graphemes(text)),
+                })
+                .collect())
+        }
+    };
+    parsing::parse_params(&format!("\"{}\"", escape(&reversed)))
+}
+
+/// Reverses the text by grapheme clusters.
+fn rev_graphemes(text: &str) -> String {
+    text.graphemes(true).rev().collect()
 }
 
 /// An operation to perform on a string.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringMultOperation {
     /// The type of operation to perform.
     pub operation_type: OperationType,
     /// The argument to the operation.
+    #[cfg_attr(feature = "serde", serde(with = "argument"))]
     pub argument: Either<isize, f64>,
+    /// Condition on the numbers the operation applies to; all numbers if `None`.
+    pub predicate: Option<Predicate>,
+}
+
+/// A condition on a number.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Predicate {
+    /// The number compares with the value.
+    Compare(Comparison, f64),
+    /// The number is within the range.
+    Range {
+        /// The lower bound, included.
+        start: f64,
+        /// The upper bound.
+        end: f64,
+        /// Whether the upper bound is included.
+        inclusive: bool,
+    },
+    /// The condition does not hold.
+    Not(Box<Predicate>),
+    /// Both conditions hold.
+    And(Box<Predicate>, Box<Predicate>),
+    /// Any of the conditions holds.
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    /// Checks whether the number satisfies the condition.
+    pub fn matches(&self, n: f64) -> bool {
+        match self {
+            Predicate::Compare(comparison, value) => match comparison {
+                Comparison::Less => n < *value,
+                Comparison::LessOrEqual => n <= *value,
+                Comparison::Greater => n > *value,
+                Comparison::GreaterOrEqual => n >= *value,
+                Comparison::Equal => n == *value,
+                Comparison::NotEqual => n != *value,
+            },
+            Predicate::Range {
+                start,
+                end,
+                inclusive,
+            } => n >= *start && (n < *end || *inclusive && n == *end),
+            Predicate::Not(predicate) => !predicate.matches(n),
+            Predicate::And(left, right) => left.matches(n) && right.matches(n),
+            Predicate::Or(left, right) => left.matches(n) || right.matches(n),
+        }
+    }
+}
+
+/// A comparison operator of a `Predicate`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "piece::Piece", into = "piece::Piece")
+)]
 /// A piece of a string params that is being operated on.
 pub enum ParamsPiece {
     /// A number.
-    Num(f64),
+    Num {
+        /// The value of the number.
+        value: f64,
+        /// Whether the number is an integer: written without a decimal point, and not made
+        /// fractional by an operation.
+        integer: bool,
+    },
     /// Not a number.
     Str(String),
 }
 
 /// The type of operation to perform.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperationType {
     /// Multiply the number at the given index by the argument.
     Mult(Option<isize>),
     /// Multiply all numbers by the argument.
     MultAll,
+    /// Clamp the number at the given index, or all numbers if `None`, between the given lower
+    /// bound and the argument.
+    Clamp(Option<isize>, f64),
+    /// Replace the number at the given index, or all numbers if `None`, with the argument if it
+    /// is smaller, e.g. `min 10` limits numbers to at most 10.
+    Min(Option<isize>),
+    /// Replace the number at the given index, or all numbers if `None`, with the argument if it
+    /// is greater, e.g. `max 0` limits numbers to at least 0.
+    Max(Option<isize>),
+    /// Raise the number at the given index, or all numbers if `None`, to the power of the argument.
+    Pow(Option<isize>),
+    /// Replace the number at the given index, or all numbers if `None`, with the remainder of its
+    /// Euclidean division by the argument, e.g. `-1 % 3` is `2`.
+    Mod(Option<isize>),
+    /// Replace the number at the given index, or all numbers if `None`, with the quotient of its
+    /// Euclidean division by the argument, e.g. `-7 / 2` is `-4`.
+    IntDiv(Option<isize>),
     /// Duplicate the string times the argument.
     Duplicate,
+    /// Duplicate the string times the argument, using the given options.
+    DuplicateWith(DuplicateOptions),
+    /// Change the text of the string, leaving numbers intact.
+    Text(TextOperation),
+    /// Apply the operations of the named function with the given arguments; the argument is not
+    /// used.
+    Call(String, Vec<f64>),
+}
+
+/// A function defined with `fn name(params) = operations`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+    /// Names of the parameters.
+    pub params: Vec<String>,
+    /// Operations of the function body.
+    pub operations: Vec<StringMultOperation>,
+    /// Pairs of an index in `operations` and an index in `params`; the operation takes the value
+    /// of that parameter as its argument.
+    pub bindings: Vec<(usize, usize)>,
+}
+
+/// An operation on the parts of a string that are not numbers.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextOperation {
+    /// Convert text to upper case; the argument is not used.
+    Upper,
+    /// Convert text to lower case; the argument is not used.
+    Lower,
+    /// Capitalize the first letter of each word and lower the rest; the argument is not used.
+    Title,
+    /// Remove whitespace at the start and the end of the string; the argument is not used.
+    Trim,
+    /// Pad the string with spaces up to the argument width in grapheme clusters; a negative
+    /// width pads on the left.
+    Pad,
+    /// Replace all occurrences of the first string with the second one; the argument is not used.
+    Replace(String, String),
+}
+
+/// Options of the duplication operator.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateOptions {
+    /// Text placed between the copies.
+    pub separator: Option<String>,
+    /// Changes applied to the numbers of each subsequent copy.
+    pub steps: Vec<DuplicateStep>,
+    /// How the string is reversed for a negative argument; the evaluation default if `None`.
+    pub reverse: Option<ReverseMode>,
+}
+
+/// How a string is reversed by a duplication with a negative argument.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReverseMode {
+    /// Reverse the characters and parse the numbers again, e.g. `-12.5x` becomes `x5.21-`.
+    Chars,
+    /// Reverse the grapheme clusters and parse the numbers again, keeping emoji and combining
+    /// marks intact.
+    #[default]
+    Graphemes,
+    /// Reverse the order of pieces and the grapheme clusters of text pieces, keeping numbers
+    /// intact, e.g. `-12.5x` becomes `x-12.5`.
+    Pieces,
+}
+
+/// A change applied to numbers of each subsequent copy of a duplicated string.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateStep {
+    /// Index of the number to change, all numbers if `None`.
+    pub index: Option<isize>,
+    /// How the number changes.
+    pub kind: StepKind,
+    /// The change per copy.
+    pub amount: f64,
+}
+
+/// How a `DuplicateStep` changes the numbers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StepKind {
+    /// The n-th copy gets `amount * n` added.
+    Offset,
+    /// The n-th copy gets multiplied by `amount` to the power of n.
+    Scale,
+}
+
+/// A script of decimal digits that numbers can be written in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DigitScript {
+    /// `0123456789`
+    #[default]
+    Ascii,
+    /// `٠١٢٣٤٥٦٧٨٩`
+    ArabicIndic,
+    /// `۰۱۲۳۴۵۶۷۸۹`
+    ExtendedArabicIndic,
+    /// `०१२३४५६७८९`
+    Devanagari,
+    /// `০১২৩৪৫৬৭৮৯`
+    Bengali,
+    /// `๐๑๒๓๔๕๖๗๘๙`
+    Thai,
+    /// `０１２３４５６７８９`
+    FullWidth,
+}
+
+impl DigitScript {
+    const ALL: [DigitScript; 7] = [
+        DigitScript::Ascii,
+        DigitScript::ArabicIndic,
+        DigitScript::ExtendedArabicIndic,
+        DigitScript::Devanagari,
+        DigitScript::Bengali,
+        DigitScript::Thai,
+        DigitScript::FullWidth,
+    ];
+
+    /// The digit zero of the script.
+    fn zero(self) -> char {
+        match self {
+            DigitScript::Ascii => '0',
+            DigitScript::ArabicIndic => '٠',
+            DigitScript::ExtendedArabicIndic => '۰',
+            DigitScript::Devanagari => '०',
+            DigitScript::Bengali => '০',
+            DigitScript::Thai => '๐',
+            DigitScript::FullWidth => '０',
+        }
+    }
+
+    /// Returns the script of the digit `c` and its value, or `None` if `c` is not a digit.
+    pub fn of(c: char) -> Option<(DigitScript, u32)> {
+        DigitScript::ALL.into_iter().find_map(|script| {
+            let value = (c as u32).checked_sub(script.zero() as u32)?;
+            (value < 10).then_some((script, value))
+        })
+    }
+
+    /// Returns the script of the first non-ASCII digit in `text`, or `Ascii` if there is none.
+    pub fn detect(text: &str) -> DigitScript {
+        text.chars()
+            .filter_map(DigitScript::of)
+            .map(|(script, _)| script)
+            .find(|script| *script != DigitScript::Ascii)
+            .unwrap_or_default()
+    }
+
+    /// Replaces digits of any script in `text` with ASCII digits.
+    pub(crate) fn to_ascii(text: &str) -> String {
+        text.chars()
+            .map(|c| match DigitScript::of(c) {
+                Some((_, value)) => char::from_digit(value, 10).unwrap_or(c),
+                None => c,
+            })
+            .collect()
+    }
+
+    /// Replaces ASCII digits in `text` with digits of this script.
+    pub(crate) fn render(self, text: &str) -> String {
+        text.chars()
+            .map(|c| match c.to_digit(10) {
+                Some(value) => char::from_u32(self.zero() as u32 + value).unwrap_or(c),
+                None => c,
+            })
+            .collect()
+    }
+}
+
+/// Escapes quote marks and backslashes in `text` for use inside a string parameter.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
 /// Converts a vector of `ParamsPiece` to a string.
 pub(crate) fn to_string(parts: &[ParamsPiece]) -> String {
+    to_string_in(parts, DigitScript::Ascii)
+}
+
+/// Converts a vector of `ParamsPiece` to a string, writing numbers in the given script.
+pub(crate) fn to_string_in(parts: &[ParamsPiece], digits: DigitScript) -> String {
     parts
         .iter()
         .map(|p| match p {
-            ParamsPiece::Num(n) => {
-                let rounded = format!("{n:.8}");
+            ParamsPiece::Num { value, .. } => {
+                let rounded = format!("{value:.8}");
                 let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
-                trimmed.to_string()
+                match digits {
+                    DigitScript::Ascii => trimmed.to_string(),
+                    _ => digits.render(trimmed),
+                }
             }
             ParamsPiece::Str(text) => text.to_string(),
         })
         .collect::<String>()
 }
+
+/// Serialization of `ParamsPiece` as `{"Num": {"value": 3.0, "integer": true}}` or
+/// `{"Str": " cups"}`; `{"Num": 3.0}` is read as well, as an integer if it has no fractional part.
+#[cfg(feature = "serde")]
+mod piece {
+    use super::ParamsPiece;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Piece {
+        Num(Number),
+        Str(String),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Number {
+        Flagged { value: f64, integer: bool },
+        Bare(f64),
+    }
+
+    impl From<ParamsPiece> for Piece {
+        fn from(piece: ParamsPiece) -> Self {
+            match piece {
+                ParamsPiece::Num { value, integer } => {
+                    Piece::Num(Number::Flagged { value, integer })
+                }
+                ParamsPiece::Str(text) => Piece::Str(text),
+            }
+        }
+    }
+
+    impl From<Piece> for ParamsPiece {
+        fn from(piece: Piece) -> Self {
+            match piece {
+                Piece::Num(Number::Flagged { value, integer }) => {
+                    ParamsPiece::Num { value, integer }
+                }
+                Piece::Num(Number::Bare(value)) => ParamsPiece::Num {
+                    value,
+                    integer: value.fract() == 0.0,
+                },
+                Piece::Str(text) => ParamsPiece::Str(text),
+            }
+        }
+    }
+}
+
+/// Serialization of `StringMultOperation::argument` as `{"int": 2}` for `Either::Left` and
+/// `{"float": 1.5}` for `Either::Right`.
+#[cfg(feature = "serde")]
+mod argument {
+    use super::Either;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Argument {
+        Int(isize),
+        Float(f64),
+    }
+
+    pub fn serialize<S: Serializer>(
+        argument: &Either<isize, f64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match *argument {
+            Either::Left(n) => Argument::Int(n),
+            Either::Right(n) => Argument::Float(n),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Either<isize, f64>, D::Error> {
+        Ok(match Argument::deserialize(deserializer)? {
+            Argument::Int(n) => Either::Left(n),
+            Argument::Float(n) => Either::Right(n),
+        })
+    }
+}
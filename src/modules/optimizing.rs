@@ -0,0 +1,122 @@
+//! Provides simplification of `StringMultCommand` operations
+
+use super::evaluating::{EvalContext, IntegerPolicy};
+use super::{Either, OperationType, StringMultCommand, StringMultOperation};
+
+/// Simplifies a command so that it evaluates with the default `EvalContext` to the same result.
+///
+/// See `optimize_with`.
+pub fn optimize(command: &StringMultCommand) -> StringMultCommand {
+    optimize_with(command, &EvalContext::default())
+}
+
+/// Simplifies a command so that it evaluates with `context` to the same result.
+///
+/// Duplications are moved after the following operations on all numbers, e.g. `*** 2 ** 3`
+/// becomes `** 3 *** 2`, unless the output size is limited. Consecutive `**`, as well as
+/// consecutive `*` on the same index, are then folded into one if the result stays exact,
+/// i.e. both factors are at least 1 in magnitude and one of them is a power of two, and results
+/// are not rounded by `IntegerPolicy`. A command that fails keeps failing, possibly with a
+/// different error.
+pub fn optimize_with(command: &StringMultCommand, context: &EvalContext) -> StringMultCommand {
+    let mut operations = command.operations.clone();
+    if context.limits.max_output_bytes.is_none() {
+        move_duplications(&mut operations);
+    }
+    if context.integer_policy == IntegerPolicy::Float {
+        operations = fold_multiplications(operations);
+    }
+    StringMultCommand {
+        operations,
+        ..command.clone()
+    }
+}
+
+/// Moves duplications after the operations on all numbers that follow them.
+fn move_duplications(operations: &mut [StringMultOperation]) {
+    let mut i = 0;
+    while i + 1 < operations.len() {
+        if is_plain_duplication(&operations[i]) && applies_to_all(&operations[i + 1]) {
+            operations.swap(i, i + 1);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Returns whether the operation only repeats the string, keeping its numbers as they are.
+fn is_plain_duplication(operation: &StringMultOperation) -> bool {
+    let times = match operation.argument {
+        Either::Left(times) => times,
+        Either::Right(_) => return false,
+    };
+    match &operation.operation_type {
+        OperationType::Duplicate => times > 0,
+        OperationType::DuplicateWith(options) => times > 0 && options.steps.is_empty(),
+        _ => false,
+    }
+}
+
+/// Returns whether the operation changes every number independently of its index.
+fn applies_to_all(operation: &StringMultOperation) -> bool {
+    matches!(
+        operation.operation_type,
+        OperationType::MultAll
+            | OperationType::Clamp(None, _)
+            | OperationType::Min(None)
+            | OperationType::Max(None)
+            | OperationType::Pow(None)
+            | OperationType::Mod(None)
+            | OperationType::IntDiv(None)
+    )
+}
+
+/// Folds consecutive multiplications of the same numbers.
+fn fold_multiplications(operations: Vec<StringMultOperation>) -> Vec<StringMultOperation> {
+    let mut result: Vec<StringMultOperation> = Vec::with_capacity(operations.len());
+    for operation in operations {
+        if let Some(last) = result.last_mut() {
+            if let Some(factor) = folded_factor(last, &operation) {
+                last.argument = Either::Right(factor);
+                continue;
+            }
+        }
+        result.push(operation);
+    }
+    result
+}
+
+/// Returns the factor replacing two multiplications of the same numbers, if it is exact.
+fn folded_factor(first: &StringMultOperation, second: &StringMultOperation) -> Option<f64> {
+    let same_numbers = match (&first.operation_type, &second.operation_type) {
+        (OperationType::MultAll, OperationType::MultAll) => {
+            first.predicate.is_none() && second.predicate.is_none()
+        }
+        (OperationType::Mult(a), OperationType::Mult(b)) => a.unwrap_or(0) == b.unwrap_or(0),
+        _ => false,
+    };
+    if !same_numbers {
+        return None;
+    }
+    let (a, b) = (factor(first), factor(second));
+    let product = a * b;
+    let exact = a.abs() >= 1.0
+        && b.abs() >= 1.0
+        && (is_power_of_two(a) || is_power_of_two(b))
+        && product.is_finite();
+    exact.then_some(product)
+}
+
+/// Retrieves the argument of a multiplication.
+fn factor(operation: &StringMultOperation) -> f64 {
+    match operation.argument {
+        Either::Left(arg) => arg as f64,
+        Either::Right(arg) => arg,
+    }
+}
+
+/// Returns whether multiplying by `n` only changes the exponent of a number.
+fn is_power_of_two(n: f64) -> bool {
+    n.is_normal() && n.to_bits() & ((1 << 52) - 1) == 0
+}
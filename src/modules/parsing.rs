@@ -1,11 +1,18 @@
 //! Provides parsing functionality for retrieving `StringMultCommand` from string
 
-use super::{Either, OperationType, ParamsPiece, StringMultOperation};
+use super::{
+    Comparison, DigitScript, DuplicateOptions, DuplicateStep, Either, Function, OperationType,
+    ParamsPiece, Predicate, ReverseMode, StepKind, StringMultOperation, TextOperation,
+};
 use crate::{Rule, StringMultGrammar};
-use pest::{iterators::Pairs, Parser};
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
 use thiserror::Error;
 
-use super::StringMultCommand;
+use super::{Span, Spanned, StringMultCommand};
+use std::collections::HashMap;
 
 #[derive(Error, Debug)]
 /// An error that can occur during parsing.
@@ -19,6 +26,18 @@ pub enum ParseError {
     #[error("unexpected rule {0}")]
     /// The rule (provided as String) was unexpected
     UnexpectedRule(String),
+    #[error("duplication option does not follow duplication")]
+    /// Separator, step or reverse mode is not preceded by a duplication operation
+    OptionWithoutDuplication,
+    #[error("unknown macro '{0}' at {1}")]
+    /// Invoked macro (provided as name) is not defined
+    UnknownMacro(String, Span),
+    #[error("macro '{0}' invokes itself at {1}")]
+    /// Macro (provided as name) is invoked during its own expansion
+    RecursiveMacro(String, Span),
+    #[error("unknown parameter '{0}' at {1}")]
+    /// Parameter (provided as name) is used outside of a function that declares it
+    UnknownParameter(String, Span),
     #[error("operation argument precedes operation")]
     /// Argument for operation precedes it (incorrect order)
     ArgumentWithoutOperation,
@@ -33,6 +52,37 @@ pub enum ParseError {
     Unknown,
 }
 
+impl ParseError {
+    /// Returns a stable identifier of the kind of the error, e.g. `unknown_macro`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::NoCommandsList => "no_commands_list",
+            ParseError::WrongCommand(_) => "wrong_command",
+            ParseError::UnexpectedRule(_) => "unexpected_rule",
+            ParseError::OptionWithoutDuplication => "option_without_duplication",
+            ParseError::UnknownMacro(_, _) => "unknown_macro",
+            ParseError::RecursiveMacro(_, _) => "recursive_macro",
+            ParseError::UnknownParameter(_, _) => "unknown_parameter",
+            ParseError::ArgumentWithoutOperation => "argument_without_operation",
+            ParseError::ParseFloatError(_) => "invalid_float",
+            ParseError::ParseIntError(_) => "invalid_int",
+            ParseError::Unknown => "unknown_parse_error",
+        }
+    }
+}
+
+/// Serialized as `{"code": ..., "message": ...}`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ParseError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("ParseError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
 /// Parses just the `Vec<StrPiece>` params.
 pub(crate) fn parse_params(input: &str) -> Result<Vec<ParamsPiece>, ParseError> {
     let data = StringMultGrammar::parse(Rule::str_param, input);
@@ -44,72 +94,196 @@ pub(crate) fn parse_params(input: &str) -> Result<Vec<ParamsPiece>, ParseError>
         .next()
         .ok_or(ParseError::WrongCommand(input.to_string()))?
         .into_inner();
-
-    let mut pieces: Vec<ParamsPiece> = Vec::new();
-    for part in inner {
-        match part.as_rule() {
-            Rule::num => pieces.push(ParamsPiece::Num(part.as_str().parse::<f64>()?)),
-            Rule::inner_str_text => pieces.push(ParamsPiece::Str(part.as_str().to_string())),
-            r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
-        }
-    }
-    Ok(pieces)
+    parse_pieces(inner)
 }
 
 /// Parses a list of commands.
+///
+/// Macros defined with `macro name = operations` anywhere in the list are expanded in place of
+/// their `@name` invocations; the definitions themselves and `#` comments produce no results. Function definitions
+/// only produce a result if they fail to parse; use `parse_functions` to retrieve them.
 pub fn parse_list(input: &str) -> Result<Vec<Result<StringMultCommand, ParseError>>, ParseError> {
+    let results = parse_list_spanned(input)?;
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Parses a list of commands like `parse_list`, along with the span of each result in `input`.
+///
+/// Spans of commands and wrong commands do not include the whitespace around them.
+pub fn parse_list_spanned(
+    input: &str,
+) -> Result<Vec<Spanned<Result<StringMultCommand, ParseError>>>, ParseError> {
     let mut results = Vec::new();
 
-    let data = StringMultGrammar::parse(Rule::commands_list, input);
-    match data {
-        Ok(mut data) => {
-            let inner = data.next().ok_or(ParseError::NoCommandsList)?.into_inner();
-            for part in inner {
-                if part.as_rule() == Rule::wrong_command {
-                    results.push(Err(ParseError::WrongCommand(part.as_str().to_string())));
-                } else {
-                    results.push(parse_command(part.as_str()));
+    let inner = parse_commands_list(input)?;
+    let macros = collect_macros(inner.clone());
+    for part in inner {
+        let text = part.as_str();
+        let start = part.as_span().start() + text.len() - text.trim_start().len();
+        let span = Span {
+            start,
+            end: start + text.trim().len(),
+        };
+        match part.as_rule() {
+            Rule::wrong_command => {
+                results.push((span, Err(ParseError::WrongCommand(text.to_string()))));
+            }
+            Rule::macro_def | Rule::comment => continue,
+            Rule::fn_def => {
+                if let Err(e) = parse_function_pair(part, &macros) {
+                    results.push((span, Err(e)));
                 }
             }
-            Ok(results)
+            _ => results.push((span, parse_command_pair(part, &macros))),
         }
-        Err(_) => Err(ParseError::NoCommandsList),
     }
+    Ok(results)
+}
+
+/// Parses the functions defined with `fn name(params) = operations` in a list of commands.
+///
+/// Definitions that fail to parse are skipped, as `parse_list` reports them.
+pub fn parse_functions(input: &str) -> Result<HashMap<String, Function>, ParseError> {
+    let inner = parse_commands_list(input)?;
+    let macros = collect_macros(inner.clone());
+    Ok(inner
+        .filter(|p| p.as_rule() == Rule::fn_def)
+        .filter_map(|p| parse_function_pair(p, &macros).ok())
+        .collect())
 }
 
 /// Parses a string into a `StringMultCommand`.
 pub fn parse_command(input: &str) -> Result<StringMultCommand, ParseError> {
     let data = StringMultGrammar::parse(Rule::command, input);
-    let inner: Pairs<'_, Rule> = match data {
+    let command = match data {
         Ok(mut data) => data
             .next()
-            .ok_or(ParseError::WrongCommand(input.to_string()))?
-            .into_inner(),
+            .ok_or(ParseError::WrongCommand(input.to_string()))?,
         Err(_) => return Err(ParseError::WrongCommand(input.to_string())),
     };
+    parse_command_pair(command, &Macros::new())
+}
 
-    let mut pieces: Vec<ParamsPiece> = Vec::new();
+/// Macro bodies (`operations` pairs) by macro name.
+type Macros<'i> = HashMap<&'i str, Pair<'i, Rule>>;
+
+/// Names available while parsing operations.
+struct Scope<'i, 'm> {
+    /// Macros that can be expanded.
+    macros: &'m Macros<'i>,
+    /// Names of the macros being expanded.
+    stack: Vec<&'i str>,
+    /// Parameters of the function being parsed.
+    params: Vec<&'i str>,
+    /// Operations that take a parameter as their argument, see `Function::bindings`.
+    bindings: Vec<(usize, usize)>,
+}
+
+impl<'i, 'm> Scope<'i, 'm> {
+    fn new(macros: &'m Macros<'i>) -> Self {
+        Scope {
+            macros,
+            stack: Vec::new(),
+            params: Vec::new(),
+            bindings: Vec::new(),
+        }
+    }
+}
+
+/// Parses the inner pairs of a `commands_list`.
+fn parse_commands_list(input: &str) -> Result<Pairs<'_, Rule>, ParseError> {
+    match StringMultGrammar::parse(Rule::commands_list, input) {
+        Ok(mut data) => Ok(data.next().ok_or(ParseError::NoCommandsList)?.into_inner()),
+        Err(_) => Err(ParseError::NoCommandsList),
+    }
+}
+
+/// Collects the macros defined in the inner pairs of a `commands_list`.
+fn collect_macros(pairs: Pairs<'_, Rule>) -> Macros<'_> {
+    let mut macros = Macros::new();
+    for part in pairs.filter(|p| p.as_rule() == Rule::macro_def) {
+        let mut inner_parts = part.into_inner();
+        if let (Some(name), Some(body)) = (inner_parts.next(), inner_parts.next()) {
+            macros.insert(name.as_str(), body);
+        }
+    }
+    macros
+}
+
+/// Parses a `fn_def` pair into the function name and `Function`, expanding `macros`.
+fn parse_function_pair<'i>(
+    definition: Pair<'i, Rule>,
+    macros: &Macros<'i>,
+) -> Result<(String, Function), ParseError> {
+    let mut scope = Scope::new(macros);
+    let mut name = None;
+    let mut operations = Vec::new();
+    for part in definition.into_inner() {
+        match part.as_rule() {
+            Rule::ident if name.is_none() => name = Some(part.as_str().to_string()),
+            Rule::ident => scope.params.push(part.as_str()),
+            Rule::operations => parse_operations(part.into_inner(), &mut scope, &mut operations)?,
+            r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
+        }
+    }
+    let function = Function {
+        params: scope.params.iter().map(|p| p.to_string()).collect(),
+        operations,
+        bindings: scope.bindings,
+    };
+    Ok((name.ok_or(ParseError::Unknown)?, function))
+}
+
+/// Parses a `command` pair into a `StringMultCommand`, expanding `macros`.
+fn parse_command_pair<'i>(
+    command: Pair<'i, Rule>,
+    macros: &Macros<'i>,
+) -> Result<StringMultCommand, ParseError> {
+    let mut inner = command.into_inner();
+    let param = inner.next().ok_or(ParseError::Unknown)?;
+    if param.as_rule() != Rule::str_param {
+        return Err(ParseError::UnexpectedRule(format!("{:?}", param.as_rule())));
+    }
+    let digits = DigitScript::detect(param.as_str());
+    let params = parse_pieces(param.into_inner())?;
 
     let mut operations: Vec<StringMultOperation> = Vec::new();
+    parse_operations(inner, &mut Scope::new(macros), &mut operations)?;
+    Ok(StringMultCommand {
+        params,
+        operations,
+        digits,
+    })
+}
+
+/// Parses the pairs of operations outside of any list into a function with the given parameters;
+/// macro calls are not resolved.
+pub(crate) fn parse_operation_pairs<'i>(
+    pairs: Vec<Pair<'i, Rule>>,
+    params: &[&'i str],
+) -> Result<Function, ParseError> {
+    let macros = Macros::new();
+    let mut scope = Scope::new(&macros);
+    scope.params = params.to_vec();
+    let mut operations = Vec::new();
+    parse_operations(pairs, &mut scope, &mut operations)?;
+    Ok(Function {
+        params: params.iter().map(|p| p.to_string()).collect(),
+        operations,
+        bindings: scope.bindings,
+    })
+}
+
+/// Parses operation pairs into `operations`, resolving macros and parameters from `scope`.
+fn parse_operations<'i>(
+    pairs: impl IntoIterator<Item = Pair<'i, Rule>>,
+    scope: &mut Scope<'i, '_>,
+    operations: &mut Vec<StringMultOperation>,
+) -> Result<(), ParseError> {
     let mut operation: Option<OperationType> = None;
 
-    for part in inner {
+    for part in pairs {
         match part.as_rule() {
-            Rule::str_param => {
-                pieces = Vec::new();
-                for inner_part in part.into_inner() {
-                    match inner_part.as_rule() {
-                        Rule::num => {
-                            pieces.push(ParamsPiece::Num(inner_part.as_str().parse::<f64>()?));
-                        }
-                        Rule::inner_str_text => {
-                            pieces.push(ParamsPiece::Str(inner_part.as_str().to_string()));
-                        }
-                        r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
-                    }
-                }
-            }
-
             Rule::mult => {
                 let mut inner_parts = part.into_inner();
                 let index = match inner_parts.next() {
@@ -119,14 +293,106 @@ pub fn parse_command(input: &str) -> Result<StringMultCommand, ParseError> {
                 operation = Some(OperationType::Mult(Some(index)));
             }
             Rule::multAll => operation = Some(OperationType::MultAll),
+            Rule::pow | Rule::modulo | Rule::intDiv => {
+                let rule = part.as_rule();
+                let index = match part.into_inner().next() {
+                    Some(inner_part) => inner_part.as_str().parse::<isize>()?,
+                    None => 0,
+                };
+                operation = match rule {
+                    Rule::pow => Some(OperationType::Pow(Some(index))),
+                    Rule::modulo => Some(OperationType::Mod(Some(index))),
+                    _ => Some(OperationType::IntDiv(Some(index))),
+                };
+            }
+            Rule::powAll => operation = Some(OperationType::Pow(None)),
+            Rule::moduloAll => operation = Some(OperationType::Mod(None)),
+            Rule::intDivAll => operation = Some(OperationType::IntDiv(None)),
+            Rule::clamp => {
+                let mut index = None;
+                let mut low = 0.0;
+                for inner_part in part.into_inner() {
+                    match inner_part.as_rule() {
+                        Rule::int => index = Some(inner_part.as_str().parse::<isize>()?),
+                        Rule::num => low = parse_num(inner_part.as_str())?,
+                        r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
+                    }
+                }
+                operation = Some(OperationType::Clamp(index, low));
+            }
+            Rule::min | Rule::max => {
+                let rule = part.as_rule();
+                let index = match part.into_inner().next() {
+                    Some(inner_part) => Some(inner_part.as_str().parse::<isize>()?),
+                    None => None,
+                };
+                operation = match rule {
+                    Rule::min => Some(OperationType::Min(index)),
+                    _ => Some(OperationType::Max(index)),
+                };
+            }
             Rule::duplicate => operation = Some(OperationType::Duplicate),
 
+            Rule::separator => {
+                let separator = str_param_text(&mut part.into_inner())?;
+                duplicate_options(operations)?.separator = Some(separator);
+            }
+            Rule::step => {
+                let mut kind = StepKind::Offset;
+                let mut index = None;
+                let mut amount = 0.0;
+                for inner_part in part.into_inner() {
+                    match inner_part.as_rule() {
+                        Rule::step_kind if inner_part.as_str() == "scale" => {
+                            kind = StepKind::Scale;
+                        }
+                        Rule::step_kind => kind = StepKind::Offset,
+                        Rule::int => index = Some(inner_part.as_str().parse::<isize>()?),
+                        Rule::num => amount = parse_num(inner_part.as_str())?,
+                        r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
+                    }
+                }
+                duplicate_options(operations)?.steps.push(DuplicateStep {
+                    index,
+                    kind,
+                    amount,
+                });
+            }
+
+            Rule::reverse => {
+                let mode = match part.into_inner().next().map(|p| p.as_str()) {
+                    Some("chars") => ReverseMode::Chars,
+                    Some("graphemes") => ReverseMode::Graphemes,
+                    Some("pieces") => ReverseMode::Pieces,
+                    _ => return Err(ParseError::Unknown),
+                };
+                duplicate_options(operations)?.reverse = Some(mode);
+            }
+
+            Rule::filter => {
+                let predicate = part.into_inner().next().ok_or(ParseError::Unknown)?;
+                let last = operations.last_mut().ok_or(ParseError::Unknown)?;
+                last.predicate = Some(parse_predicate(predicate)?);
+            }
+            Rule::pad => operation = Some(OperationType::Text(TextOperation::Pad)),
+            Rule::replace => {
+                let mut inner_parts = part.into_inner();
+                let from = str_param_text(&mut inner_parts)?;
+                let to = str_param_text(&mut inner_parts)?;
+                operations.push(text_operation(TextOperation::Replace(from, to)));
+            }
+            Rule::upper => operations.push(text_operation(TextOperation::Upper)),
+            Rule::lower => operations.push(text_operation(TextOperation::Lower)),
+            Rule::title => operations.push(text_operation(TextOperation::Title)),
+            Rule::trim => operations.push(text_operation(TextOperation::Trim)),
+
             Rule::int => {
                 let int = part.as_str().parse::<isize>()?;
                 if let Some(op) = operation {
                     operations.push(StringMultOperation {
                         operation_type: op,
                         argument: Either::Left(int),
+                        predicate: None,
                     });
                     operation = None;
                 } else {
@@ -134,11 +400,12 @@ pub fn parse_command(input: &str) -> Result<StringMultCommand, ParseError> {
                 }
             }
             Rule::num => {
-                let num = part.as_str().parse::<f64>()?;
+                let num = parse_num(part.as_str())?;
                 if let Some(op) = operation {
                     operations.push(StringMultOperation {
                         operation_type: op,
                         argument: Either::Right(num),
+                        predicate: None,
                     });
                     operation = None;
                 } else {
@@ -146,11 +413,184 @@ pub fn parse_command(input: &str) -> Result<StringMultCommand, ParseError> {
                 }
             }
 
+            Rule::param_ref => {
+                let span = Span::from(part.as_span());
+                let name = part.as_str();
+                let param = scope
+                    .params
+                    .iter()
+                    .position(|p| *p == name)
+                    .ok_or(ParseError::UnknownParameter(name.to_string(), span))?;
+                if let Some(op) = operation {
+                    scope.bindings.push((operations.len(), param));
+                    operations.push(StringMultOperation {
+                        operation_type: op,
+                        argument: Either::Left(0),
+                        predicate: None,
+                    });
+                    operation = None;
+                } else {
+                    return Err(ParseError::ArgumentWithoutOperation);
+                }
+            }
+
+            Rule::macro_call => {
+                let span = Span::from(part.as_span());
+                let name = part
+                    .into_inner()
+                    .next()
+                    .ok_or(ParseError::Unknown)?
+                    .as_str();
+                if scope.stack.contains(&name) {
+                    return Err(ParseError::RecursiveMacro(name.to_string(), span));
+                }
+                let body = scope
+                    .macros
+                    .get(name)
+                    .ok_or(ParseError::UnknownMacro(name.to_string(), span))?;
+                scope.stack.push(name);
+                parse_operations(body.clone().into_inner(), scope, operations)?;
+                scope.stack.pop();
+            }
+            Rule::fn_call => {
+                let mut inner_parts = part.into_inner();
+                let name = inner_parts.next().ok_or(ParseError::Unknown)?.as_str();
+                let arguments = inner_parts
+                    .map(|p| parse_num(p.as_str()))
+                    .collect::<Result<Vec<f64>, ParseError>>()?;
+                operations.push(StringMultOperation {
+                    operation_type: OperationType::Call(name.to_string(), arguments),
+                    argument: Either::Left(0),
+                    predicate: None,
+                });
+            }
+
             r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
         }
     }
-    Ok(StringMultCommand {
-        params: pieces,
-        operations,
-    })
+    Ok(())
+}
+
+/// Parses the inner pairs of a `str_param` into pieces.
+fn parse_pieces(pairs: Pairs<'_, Rule>) -> Result<Vec<ParamsPiece>, ParseError> {
+    let mut pieces: Vec<ParamsPiece> = Vec::new();
+    for part in pairs {
+        match part.as_rule() {
+            Rule::num => pieces.push(ParamsPiece::Num {
+                value: parse_num(part.as_str())?,
+                integer: !part.as_str().contains('.'),
+            }),
+            Rule::inner_str_text => pieces.push(ParamsPiece::Str(unescape(part.as_str()))),
+            r => return Err(ParseError::UnexpectedRule(format!("{r:?}"))),
+        }
+    }
+    Ok(pieces)
+}
+
+/// Parses a number written with digits of any supported script.
+fn parse_num(input: &str) -> Result<f64, ParseError> {
+    if input.is_ascii() {
+        Ok(input.parse::<f64>()?)
+    } else {
+        Ok(DigitScript::to_ascii(input).parse::<f64>()?)
+    }
+}
+
+/// Retrieves the options of the last duplication in `operations`, making them explicit.
+fn duplicate_options(
+    operations: &mut [StringMultOperation],
+) -> Result<&mut DuplicateOptions, ParseError> {
+    let last = operations
+        .last_mut()
+        .ok_or(ParseError::OptionWithoutDuplication)?;
+    if last.operation_type == OperationType::Duplicate {
+        last.operation_type = OperationType::DuplicateWith(DuplicateOptions::default());
+    }
+    match &mut last.operation_type {
+        OperationType::DuplicateWith(options) => Ok(options),
+        _ => Err(ParseError::OptionWithoutDuplication),
+    }
+}
+
+/// Parses a `predicate`, `conjunction` or `condition` into a `Predicate`.
+fn parse_predicate(pair: Pair<'_, Rule>) -> Result<Predicate, ParseError> {
+    let rule = pair.as_rule();
+    let mut inner = pair.into_inner();
+    match rule {
+        Rule::predicate | Rule::conjunction => {
+            let mut predicate = parse_predicate(inner.next().ok_or(ParseError::Unknown)?)?;
+            for next in inner {
+                let next = Box::new(parse_predicate(next)?);
+                predicate = match rule {
+                    Rule::predicate => Predicate::Or(Box::new(predicate), next),
+                    _ => Predicate::And(Box::new(predicate), next),
+                };
+            }
+            Ok(predicate)
+        }
+        Rule::condition | Rule::negation => {
+            let predicate = parse_predicate(inner.next().ok_or(ParseError::Unknown)?)?;
+            match rule {
+                Rule::negation => Ok(Predicate::Not(Box::new(predicate))),
+                _ => Ok(predicate),
+            }
+        }
+        Rule::comparison => {
+            let comparison = match inner.next().map(|p| p.as_str()) {
+                Some("<") => Comparison::Less,
+                Some("<=") => Comparison::LessOrEqual,
+                Some(">") => Comparison::Greater,
+                Some(">=") => Comparison::GreaterOrEqual,
+                Some("==") => Comparison::Equal,
+                Some("!=") => Comparison::NotEqual,
+                _ => return Err(ParseError::Unknown),
+            };
+            let value = parse_num(inner.next().ok_or(ParseError::Unknown)?.as_str())?;
+            Ok(Predicate::Compare(comparison, value))
+        }
+        Rule::range => {
+            let start = parse_num(inner.next().ok_or(ParseError::Unknown)?.as_str())?;
+            let inclusive = inner.next().ok_or(ParseError::Unknown)?.as_str() == "..=";
+            let end = parse_num(inner.next().ok_or(ParseError::Unknown)?.as_str())?;
+            Ok(Predicate::Range {
+                start,
+                end,
+                inclusive,
+            })
+        }
+        r => Err(ParseError::UnexpectedRule(format!("{r:?}"))),
+    }
+}
+
+/// Creates an operation for a text operator without argument.
+fn text_operation(operation: TextOperation) -> StringMultOperation {
+    StringMultOperation {
+        operation_type: OperationType::Text(operation),
+        argument: Either::Left(0),
+        predicate: None,
+    }
+}
+
+/// Retrieves the text inside the quote marks of the next `str_param` in `pairs`.
+fn str_param_text(pairs: &mut Pairs<'_, Rule>) -> Result<String, ParseError> {
+    let param = pairs.next().ok_or(ParseError::Unknown)?;
+    Ok(unescape(
+        &param.into_inner().map(|p| p.as_str()).collect::<String>(),
+    ))
+}
+
+/// Replaces escaped quote marks and backslashes in `text` with the characters themselves.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == '"' || next == '\\' => {
+                result.push(next);
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
 }
@@ -0,0 +1,247 @@
+//! Provides printing of `StringMultCommand` back into source text
+
+use std::fmt;
+
+use super::{
+    escape, Comparison, DigitScript, Either, OperationType, ParamsPiece, Predicate, ReverseMode,
+    StepKind, StringMultCommand, StringMultOperation, TextOperation,
+};
+
+/// Options of printing commands as source text.
+#[derive(Debug, Clone)]
+pub struct PrintOptions {
+    /// Write index `0` of `*`, `^`, `%` and `/` explicitly, e.g. `*[0] 2` instead of `* 2`.
+    pub explicit_indices: bool,
+    /// Separate operators from their arguments with a space, e.g. `** 2` instead of `**2`.
+    pub spaced_arguments: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            explicit_indices: true,
+            spaced_arguments: true,
+        }
+    }
+}
+
+/// Prints a command as canonical source text.
+///
+/// Parsing the printed text with `parse_command` gives back a command equal to the parsed one.
+pub fn print(command: &StringMultCommand) -> String {
+    print_with(command, &PrintOptions::default())
+}
+
+/// Prints a command as source text using the provided `PrintOptions`.
+pub fn print_with(command: &StringMultCommand, options: &PrintOptions) -> String {
+    let mut result = print_params(command);
+    for operation in &command.operations {
+        result.push(' ');
+        result.push_str(&print_operation(operation, options));
+    }
+    result
+}
+
+/// Prints a single operation as source text using the provided `PrintOptions`.
+pub fn print_operation(operation: &StringMultOperation, options: &PrintOptions) -> String {
+    print_bound_operation(operation, options, None)
+}
+
+/// Prints a single operation of a function body, writing the name of the parameter bound to it,
+/// if any, in place of its argument.
+pub(crate) fn print_bound_operation(
+    operation: &StringMultOperation,
+    options: &PrintOptions,
+    param: Option<&str>,
+) -> String {
+    let space = match options.spaced_arguments {
+        true => " ",
+        false => "",
+    };
+    let indexed = |operator: &str, index: isize| match index == 0 && !options.explicit_indices {
+        true => operator.to_string(),
+        false => format!("{operator}[{index}]"),
+    };
+    let operator = match &operation.operation_type {
+        OperationType::Mult(index) => indexed("*", index.unwrap_or(0)),
+        OperationType::MultAll => "**".to_string(),
+        OperationType::Clamp(index, low) => {
+            format!("clamp{}{space}{}", print_index(*index), print_number(*low))
+        }
+        OperationType::Min(index) => format!("min{}", print_index(*index)),
+        OperationType::Max(index) => format!("max{}", print_index(*index)),
+        OperationType::Pow(Some(index)) => indexed("^", *index),
+        OperationType::Pow(None) => "^^".to_string(),
+        OperationType::Mod(Some(index)) => indexed("%", *index),
+        OperationType::Mod(None) => "%%".to_string(),
+        OperationType::IntDiv(Some(index)) => indexed("/", *index),
+        OperationType::IntDiv(None) => "//".to_string(),
+        OperationType::Duplicate | OperationType::DuplicateWith(_) => "***".to_string(),
+        OperationType::Text(TextOperation::Pad) => "pad".to_string(),
+        OperationType::Text(text) => return print_text(text),
+        OperationType::Call(name, arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(|a| print_number(*a)).collect();
+            return format!("@{name}({})", arguments.join(", "));
+        }
+    };
+    let argument = match (param, operation.argument) {
+        (Some(param), _) => param.to_string(),
+        (None, Either::Left(arg)) => arg.to_string(),
+        (None, Either::Right(arg)) => print_number(arg),
+    };
+    // the lower bound of clamping is always separated from the upper one
+    let separator = match operation.operation_type {
+        OperationType::Clamp(_, _) => " ",
+        _ => space,
+    };
+    let mut result = format!("{operator}{separator}{argument}");
+    if let OperationType::DuplicateWith(duplicate) = &operation.operation_type {
+        if let Some(separator) = &duplicate.separator {
+            result.push_str(&format!(" sep \"{}\"", escape(separator)));
+        }
+        for step in &duplicate.steps {
+            let kind = match step.kind {
+                StepKind::Offset => "step",
+                StepKind::Scale => "scale",
+            };
+            let index = print_index(step.index);
+            result.push_str(&format!(
+                " {kind}{index}{space}{}",
+                print_number(step.amount)
+            ));
+        }
+        if let Some(mode) = duplicate.reverse {
+            let mode = match mode {
+                ReverseMode::Chars => "chars",
+                ReverseMode::Graphemes => "graphemes",
+                ReverseMode::Pieces => "pieces",
+            };
+            result.push_str(&format!(" rev {mode}"));
+        }
+    }
+    if let Some(predicate) = &operation.predicate {
+        result.push_str(&format!(" where {}", print_predicate(predicate, 0)));
+    }
+    result
+}
+
+/// Prints the string parameter of a command with its quote marks.
+fn print_params(command: &StringMultCommand) -> String {
+    let mut result = String::from("\"");
+    for (i, piece) in command.params.iter().enumerate() {
+        match piece {
+            ParamsPiece::Num { value, integer } => {
+                let mut number = print_number(*value);
+                // keep `12.0` decimal, and `1` followed by `.5` text from becoming `1.5`
+                let merges = match command.params.get(i + 1) {
+                    Some(ParamsPiece::Str(next)) => next
+                        .strip_prefix('.')
+                        .and_then(|rest| rest.chars().next())
+                        .is_some_and(|c| DigitScript::of(c).is_some()),
+                    _ => false,
+                };
+                if (merges || !integer) && !number.contains('.') {
+                    number.push_str(".0");
+                }
+                result.push_str(&command.digits.render(&number));
+            }
+            ParamsPiece::Str(text) => result.push_str(&escape(text)),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Prints a number; infinite numbers, which only come from numbers too large for `f64`, are
+/// printed as such a number.
+fn print_number(n: f64) -> String {
+    match n.is_infinite() {
+        true => format!("{}1{}", if n < 0.0 { "-" } else { "" }, "0".repeat(309)),
+        false => n.to_string(),
+    }
+}
+
+/// Prints an optional index in square brackets.
+fn print_index(index: Option<isize>) -> String {
+    match index {
+        Some(index) => format!("[{index}]"),
+        None => String::new(),
+    }
+}
+
+/// Prints a text operation.
+fn print_text(text: &TextOperation) -> String {
+    match text {
+        TextOperation::Upper => "upper".to_string(),
+        TextOperation::Lower => "lower".to_string(),
+        TextOperation::Title => "title".to_string(),
+        TextOperation::Trim => "trim".to_string(),
+        TextOperation::Pad => "pad".to_string(),
+        TextOperation::Replace(from, to) => {
+            format!("replace \"{}\" \"{}\"", escape(from), escape(to))
+        }
+    }
+}
+
+/// Prints a predicate at the given precedence level: `0` for alternatives, `1` for conjunctions
+/// and `2` for single conditions; predicates of a lower level are put in parentheses.
+fn print_predicate(predicate: &Predicate, level: u8) -> String {
+    let (text, own_level) = match predicate {
+        Predicate::Or(left, right) => (
+            format!(
+                "{} or {}",
+                print_predicate(left, 0),
+                print_predicate(right, 1)
+            ),
+            0,
+        ),
+        Predicate::And(left, right) => (
+            format!(
+                "{} and {}",
+                print_predicate(left, 1),
+                print_predicate(right, 2)
+            ),
+            1,
+        ),
+        Predicate::Not(inner) => (format!("not {}", print_predicate(inner, 2)), 2),
+        Predicate::Compare(comparison, value) => {
+            let operator = match comparison {
+                Comparison::Less => "<",
+                Comparison::LessOrEqual => "<=",
+                Comparison::Greater => ">",
+                Comparison::GreaterOrEqual => ">=",
+                Comparison::Equal => "==",
+                Comparison::NotEqual => "!=",
+            };
+            (format!("{operator} {}", print_number(*value)), 2)
+        }
+        Predicate::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let operator = match inclusive {
+                true => "..=",
+                false => "..",
+            };
+            let (start, end) = (print_number(*start), print_number(*end));
+            (format!("in {start}{operator}{end}"), 2)
+        }
+    };
+    match own_level < level {
+        true => format!("({text})"),
+        false => text,
+    }
+}
+
+impl fmt::Display for StringMultCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&print(self))
+    }
+}
+
+impl fmt::Display for StringMultOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&print_operation(self, &PrintOptions::default()))
+    }
+}
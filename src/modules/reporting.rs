@@ -0,0 +1,211 @@
+//! Provides machine-readable output of the results of a commands list
+
+use std::io;
+
+use super::evaluating::{evaluate_list_spanned, EvalContext, EvalError};
+use super::Span;
+
+/// A format of the records written by `write_records`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A JSON array of objects.
+    Json,
+    /// A JSON object per line.
+    Jsonl,
+    /// Comma separated values with a header, quoted as in RFC 4180.
+    Csv,
+    /// Tab separated values with a header; tabs, line breaks and backslashes in values are
+    /// written as `\t`, `\n`, `\r` and `\\`.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// All formats in the order of their names.
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Json,
+        OutputFormat::Jsonl,
+        OutputFormat::Csv,
+        OutputFormat::Tsv,
+    ];
+
+    /// Returns the name of the format, e.g. `json`.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+        }
+    }
+
+    /// Returns the format with the given name.
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        OutputFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+}
+
+/// The result of a command of a list.
+#[derive(Debug)]
+pub struct Record {
+    /// Line of the start of the command, starting from 1.
+    pub line: usize,
+    /// The command as written in the list.
+    pub command: String,
+    /// Span of the command in the list.
+    pub span: Span,
+    /// The output of the command or the error it failed with.
+    pub result: Result<String, EvalError>,
+}
+
+/// Evaluates a list of commands into records using the provided `EvalContext`.
+pub fn records(input: &str, context: &EvalContext) -> Result<Vec<Record>, EvalError> {
+    Ok(evaluate_list_spanned(input, context)?
+        .into_iter()
+        .map(|(span, result)| Record {
+            line: span.line_col(input).0,
+            command: input[span.start..span.end].to_string(),
+            span,
+            result: result.map(|output| output.to_string()),
+        })
+        .collect())
+}
+
+/// Columns of the CSV and TSV formats.
+const COLUMNS: [&str; 8] = [
+    "line",
+    "command",
+    "status",
+    "result",
+    "error_code",
+    "error_message",
+    "span_start",
+    "span_end",
+];
+
+/// Writes records into `writer` in the given format.
+///
+/// A JSON record is an object with `line`, `command`, `status` (`ok` or `error`), either
+/// `result` or `error` with `code` and `message`, and `span` with `start` and `end` byte
+/// offsets. CSV and TSV records have the `COLUMNS`, with empty values for the missing ones.
+pub fn write_records<W: io::Write>(
+    writer: &mut W,
+    format: OutputFormat,
+    records: &[Record],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            write!(writer, "[")?;
+            for (i, record) in records.iter().enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                write!(writer, "{separator}\n  {}", json_object(record))?;
+            }
+            match records.is_empty() {
+                true => writeln!(writer, "]"),
+                false => writeln!(writer, "\n]"),
+            }
+        }
+        OutputFormat::Jsonl => {
+            for record in records {
+                writeln!(writer, "{}", json_object(record))?;
+            }
+            Ok(())
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let (delimiter, escape): (&str, fn(&str) -> String) = match format {
+                OutputFormat::Csv => (",", escape_csv),
+                _ => ("\t", escape_tsv),
+            };
+            writeln!(writer, "{}", COLUMNS.join(delimiter))?;
+            for record in records {
+                let (result, code, message) = match &record.result {
+                    Ok(output) => (output.as_str(), "", String::new()),
+                    Err(e) => ("", e.code(), e.to_string()),
+                };
+                let values = [
+                    record.line.to_string(),
+                    escape(&record.command),
+                    status(record).to_string(),
+                    escape(result),
+                    code.to_string(),
+                    escape(&message),
+                    record.span.start.to_string(),
+                    record.span.end.to_string(),
+                ];
+                writeln!(writer, "{}", values.join(delimiter))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Returns the status of a record, `ok` or `error`.
+fn status(record: &Record) -> &'static str {
+    match record.result {
+        Ok(_) => "ok",
+        Err(_) => "error",
+    }
+}
+
+/// Writes a record as a single line JSON object.
+fn json_object(record: &Record) -> String {
+    let outcome = match &record.result {
+        Ok(output) => format!("\"result\":{}", escape_json(output)),
+        Err(e) => format!(
+            "\"error\":{{\"code\":{},\"message\":{}}}",
+            escape_json(e.code()),
+            escape_json(&e.to_string())
+        ),
+    };
+    format!(
+        "{{\"line\":{},\"command\":{},\"status\":\"{}\",{outcome},\"span\":{{\"start\":{},\"end\":{}}}}}",
+        record.line,
+        escape_json(&record.command),
+        status(record),
+        record.span.start,
+        record.span.end
+    )
+}
+
+/// Writes text as a JSON string with its quote marks.
+fn escape_json(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Quotes a CSV value if it contains a delimiter, a quote mark or a line break.
+fn escape_csv(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+/// Escapes a TSV value so that it stays within its field and line.
+fn escape_tsv(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            c => result.push(c),
+        }
+    }
+    result
+}
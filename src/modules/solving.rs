@@ -0,0 +1,190 @@
+//! Provides finding the operations that turn a string into an expected output
+
+use thiserror::Error;
+
+use super::evaluating::{evaluate_command_with, EvalContext, EvalError};
+use super::parsing::{parse_params, ParseError};
+use super::{
+    escape, DigitScript, Either, OperationType, ParamsPiece, StringMultCommand, StringMultOperation,
+};
+
+#[derive(Error, Debug)]
+/// An error that can occur during solving.
+pub enum SolveError {
+    #[error("parsing error: {0}")]
+    /// The string parameter could not be parsed
+    ParseError(#[from] ParseError),
+    #[error("evaluation error: {0}")]
+    /// The string parameter or the found operations failed to evaluate
+    EvalError(#[from] EvalError),
+    #[error("input has {0} numbers but the output has {1}")]
+    /// Input and output have different numbers of numbers
+    NumberCount(usize, usize),
+    #[error("text '{1}' of the output does not match '{0}' of the input")]
+    /// Text around the numbers differs between input and output
+    TextMismatch(String, String),
+    #[error("number at index '{0}' is zero and cannot become '{1}'")]
+    /// A zero of the input would have to become another number
+    ZeroNumber(usize, f64),
+    #[error("operations produce '{0}' instead of the expected output")]
+    /// The found factors do not reproduce the output exactly, e.g. due to rounding
+    Inexact(String),
+}
+
+/// Finds a command that turns `params` into `expected` when evaluated with the default
+/// `EvalContext`.
+///
+/// See `solve_with`.
+pub fn solve(params: &str, expected: &str) -> Result<StringMultCommand, SolveError> {
+    solve_with(params, expected, &EvalContext::default())
+}
+
+/// Finds a command that turns `params`, a `str_param` with its quote marks, into `expected`
+/// when evaluated with `context`.
+///
+/// The operations are a single `**` factor if every number changes by the same one, `*[i]`
+/// factors for the numbers that change otherwise, followed by `*** n` if `expected` repeats
+/// such a result. An unchanged string gives `** 1`. Errors explain why the output of the whole
+/// string cannot be reached.
+pub fn solve_with(
+    params: &str,
+    expected: &str,
+    context: &EvalContext,
+) -> Result<StringMultCommand, SolveError> {
+    let source = StringMultCommand {
+        params: parse_params(params.trim())?,
+        operations: Vec::new(),
+        digits: DigitScript::detect(params),
+    };
+    if expected.is_empty() && !evaluate_command_with(&source, context)?.is_empty() {
+        return Ok(with_operations(
+            &source,
+            vec![operation(OperationType::Duplicate, Either::Left(0))],
+        ));
+    }
+    let mut first_error = None;
+    for times in 1..=expected.len().max(1) {
+        let Some(part) = repeated_part(expected, times) else {
+            continue;
+        };
+        let result = solve_part(&source, part, context).and_then(|mut operations| {
+            if times > 1 {
+                operations.push(operation(
+                    OperationType::Duplicate,
+                    Either::Left(times as isize),
+                ));
+            }
+            if operations.is_empty() {
+                operations.push(operation(OperationType::MultAll, Either::Right(1.0)));
+            }
+            let command = with_operations(&source, operations);
+            match evaluate_command_with(&command, context)? {
+                output if output == expected => Ok(command),
+                output => Err(SolveError::Inexact(output)),
+            }
+        });
+        match result {
+            Ok(command) => return Ok(command),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_error.unwrap_or(SolveError::Inexact(String::new())))
+}
+
+/// Returns the part of `text` that is repeated `times` times to make it up.
+fn repeated_part(text: &str, times: usize) -> Option<&str> {
+    if !text.len().is_multiple_of(times) || !text.is_char_boundary(text.len() / times) {
+        return None;
+    }
+    let part = &text[..text.len() / times];
+    (part.repeat(times) == text).then_some(part)
+}
+
+/// Finds the multiplications turning the numbers of `source` into the ones of `expected`.
+fn solve_part(
+    source: &StringMultCommand,
+    expected: &str,
+    context: &EvalContext,
+) -> Result<Vec<StringMultOperation>, SolveError> {
+    let target = parse_params(&format!("\"{}\"", escape(expected)))?;
+    let numbers = |pieces: &[ParamsPiece]| {
+        pieces
+            .iter()
+            .filter(|p| matches!(p, ParamsPiece::Num { .. }))
+            .count()
+    };
+    if numbers(&source.params) != numbers(&target) {
+        return Err(SolveError::NumberCount(
+            numbers(&source.params),
+            numbers(&target),
+        ));
+    }
+    let mut pairs = Vec::new();
+    for (from, to) in source.params.iter().zip(&target) {
+        match (from, to) {
+            (ParamsPiece::Num { value: a, .. }, ParamsPiece::Num { value: b, .. }) => {
+                pairs.push((*a, *b))
+            }
+            (ParamsPiece::Str(a), ParamsPiece::Str(b)) if a == b => {}
+            (a, b) => {
+                let text = |p: &ParamsPiece| match p {
+                    ParamsPiece::Num { value, .. } => value.to_string(),
+                    ParamsPiece::Str(s) => s.clone(),
+                };
+                return Err(SolveError::TextMismatch(text(a), text(b)));
+            }
+        }
+    }
+    if source.params.len() != target.len() {
+        let rendered = evaluate_command_with(source, context)?;
+        return Err(SolveError::TextMismatch(rendered, expected.to_string()));
+    }
+    let mut factors = Vec::with_capacity(pairs.len());
+    for (i, (from, to)) in pairs.iter().enumerate() {
+        factors.push(match (*from, *to) {
+            (a, b) if a == b => None,
+            (0.0, b) => return Err(SolveError::ZeroNumber(i, b)),
+            (a, b) => Some(b / a),
+        });
+    }
+    // a single factor also keeps zeros, but not other unchanged numbers
+    let common = factors.iter().flatten().next().copied();
+    let shared = common.filter(|f| {
+        factors
+            .iter()
+            .zip(&pairs)
+            .all(|(factor, (from, _))| *factor == Some(*f) || *from == 0.0)
+    });
+    Ok(match shared {
+        Some(factor) => vec![operation(OperationType::MultAll, Either::Right(factor))],
+        None => factors
+            .iter()
+            .enumerate()
+            .filter_map(|(i, factor)| {
+                factor.map(|f| operation(OperationType::Mult(Some(i as isize)), Either::Right(f)))
+            })
+            .collect(),
+    })
+}
+
+/// Creates an operation without a predicate.
+fn operation(operation_type: OperationType, argument: Either<isize, f64>) -> StringMultOperation {
+    StringMultOperation {
+        operation_type,
+        argument,
+        predicate: None,
+    }
+}
+
+/// Returns `source` with the given operations.
+fn with_operations(
+    source: &StringMultCommand,
+    operations: Vec<StringMultOperation>,
+) -> StringMultCommand {
+    StringMultCommand {
+        operations,
+        ..source.clone()
+    }
+}
@@ -0,0 +1,80 @@
+//! Provides evaluation of commands embedded into arbitrary text
+
+use thiserror::Error;
+
+use super::evaluating::{evaluate_with, EvalContext, EvalError};
+use super::Span;
+
+/// Marks the start of an embedded command.
+pub const OPEN: &str = "{{";
+/// Marks the end of an embedded command.
+pub const CLOSE: &str = "}}";
+/// Stands for a literal `{{` in the rendered text.
+pub const ESCAPED_OPEN: &str = "{{{{";
+
+#[derive(Error, Debug)]
+/// An error that can occur during rendering of a template.
+pub enum TemplateError {
+    #[error("placeholder at {0} is not closed")]
+    /// Placeholder start has no matching end
+    Unclosed(Span),
+    #[error("command at {1} failed: {0}")]
+    /// Command inside the placeholder at the span failed to evaluate
+    Command(#[source] EvalError, Span),
+}
+
+/// Replaces every command between `{{` and `}}` in `input` with its result, leaving the rest of
+/// the text untouched.
+pub fn render(input: &str) -> Result<String, TemplateError> {
+    render_with(input, &EvalContext::default())
+}
+
+/// Replaces every command between `{{` and `}}` in `input` with its result using the provided
+/// `EvalContext`.
+///
+/// `}}` inside quote marks of the command does not end the placeholder, and `{{{{` is rendered
+/// as a literal `{{`.
+pub fn render_with(input: &str, context: &EvalContext) -> Result<String, TemplateError> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = 0;
+    while let Some(offset) = input[rest..].find(OPEN) {
+        let start = rest + offset;
+        if input[start..].starts_with(ESCAPED_OPEN) {
+            output.push_str(&input[rest..start]);
+            output.push_str(OPEN);
+            rest = start + ESCAPED_OPEN.len();
+            continue;
+        }
+        let inner = start + OPEN.len();
+        let end = find_close(&input[inner..])
+            .map(|i| inner + i)
+            .ok_or(TemplateError::Unclosed(Span { start, end: inner }))?;
+        let span = Span {
+            start,
+            end: end + CLOSE.len(),
+        };
+        let result = evaluate_with(input[inner..end].trim(), context)
+            .map_err(|e| TemplateError::Command(e, span))?;
+        output.push_str(&input[rest..start]);
+        output.push_str(&result);
+        rest = span.end;
+    }
+    output.push_str(&input[rest..]);
+    Ok(output)
+}
+
+/// Finds the byte offset of the first `}}` outside of quote marks, which may contain `\"`.
+fn find_close(input: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if !quoted && input[i..].starts_with(CLOSE) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,65 @@
+mod checking {
+    use string_mult::checking::*;
+    use string_mult::evaluating::EvalError;
+    use string_mult::Span;
+
+    #[test]
+    fn reports_errors_with_locations() -> anyhow::Result<()> {
+        let data = "# list\n\"x 1\" ** 2\n  garbage\n\"y\" *[0] 2\nfn f(a) = ** b\n\"z 1\" *** 3";
+        let report = check_list(data)?;
+        assert_eq!(report.commands, 5);
+        assert!(!report.is_ok());
+        let locations: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.span))
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                (3, 3, Span { start: 20, end: 27 }),
+                (4, 1, Span { start: 28, end: 38 }),
+                (5, 1, Span { start: 39, end: 53 })
+            ]
+        );
+        assert!(matches!(
+            report.diagnostics[1].error,
+            EvalError::IndexOutOfRange(0, 0)
+        ));
+        assert_eq!(
+            report.diagnostics[1].to_string(),
+            "4:1: index '0' out of range '0..0'"
+        );
+        assert_eq!(report.to_string(), "5 commands checked, 3 failed");
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_commands_stay_on_one_line() -> anyhow::Result<()> {
+        let report = check_list("bad\n\"x 1\" ** 2\nworse\r\nstill bad\n")?;
+        let lines: Vec<String> = report.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("1:1: ") && lines[0].ends_with("'bad'"));
+        assert!(lines[1].starts_with("3:1: ") && lines[1].ends_with("'worse\\r\\nstill bad'"));
+        Ok(())
+    }
+
+    #[test]
+    fn valid_list() -> anyhow::Result<()> {
+        let report = check_list("\"x 1\" ** 2\n\"ü\" upper *** 2")?;
+        assert!(report.is_ok());
+        assert_eq!(report.commands, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn columns_count_characters() -> anyhow::Result<()> {
+        let report = check_list("\"ü 1\" ** 2 \"ä\" *[1] 2")?;
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(
+            (report.diagnostics[0].line, report.diagnostics[0].column),
+            (1, 12)
+        );
+        Ok(())
+    }
+}
@@ -48,6 +48,24 @@ mod evaluation {
             Ok(())
         }
 
+        #[test]
+        fn multiply_all_where() -> anyhow::Result<()> {
+            let data = evaluate("\"5 a, 12 b, -3 c, 100 d\" ** 2 where > 10")?;
+            assert_eq!(data, "5 a, 24 b, -3 c, 200 d");
+            let data = evaluate("\"5 a, 12 b, -3 c, 100 d\" ** 2 where in 1..100")?;
+            assert_eq!(data, "10 a, 24 b, -3 c, 100 d");
+            let data = evaluate("\"5 a, 12 b, -3 c, 100 d\" ** 2 where in 1..=100")?;
+            assert_eq!(data, "10 a, 24 b, -3 c, 200 d");
+            Ok(())
+        }
+
+        #[test]
+        fn multiply_all_where_combined() -> anyhow::Result<()> {
+            let data = evaluate("\"5, 12, -3, 100\" ** -1 where < 0 or (>= 10 and not == 100)")?;
+            assert_eq!(data, "5, -12, 3, 100");
+            Ok(())
+        }
+
         #[test]
         fn multiply_all_by_float() -> anyhow::Result<()> {
             let data = evaluate("\"3packs, 10mg/l; 7,8,9\"   \t\t** 3.5")?;
@@ -56,6 +74,108 @@ mod evaluation {
         }
     }
 
+    mod arithmetic {
+        use super::*;
+        use string_mult::evaluating::EvalError;
+
+        #[test]
+        fn power() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"2 a, 3 b\" ^ 10")?, "1024 a, 3 b");
+            assert_eq!(evaluate("\"2 a, 3 b\" ^[1] 2")?, "2 a, 9 b");
+            assert_eq!(
+                evaluate("\"2 a, -3 b, 16\" ^^ 3 ^[-1] 0.5")?,
+                "8 a, -27 b, 64"
+            );
+            assert_eq!(evaluate("\"2\" ^ -1")?, "0.5");
+            Ok(())
+        }
+
+        #[test]
+        fn fractional_power_of_negative_is_err() {
+            let res = evaluate("\"4, -8\" ^^ 0.5");
+            assert!(matches!(res, Err(EvalError::FractionalPowerOfNegative(2))));
+        }
+
+        #[test]
+        fn modulo() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"13 a, -1 b\" %% 12")?, "1 a, 11 b");
+            assert_eq!(evaluate("\"13 a, -1 b\" %[1] 5 % 2")?, "1 a, 4 b");
+            assert_eq!(evaluate("\"7.5\" % 2")?, "1.5");
+            assert!(matches!(
+                evaluate("\"13\" % 0"),
+                Err(EvalError::ZeroModulus)
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn integer_division() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"7 a, -7 b\" // 2")?, "3 a, -4 b");
+            assert_eq!(evaluate("\"7 a, -7 b\" /[-1] 7 / 3")?, "2 a, -1 b");
+            assert!(matches!(
+                evaluate("\"13\" // 0"),
+                Err(EvalError::DivisionByZero)
+            ));
+            Ok(())
+        }
+    }
+
+    mod bounds {
+        use super::*;
+        use string_mult::evaluating::EvalError;
+
+        #[test]
+        fn clamp_all() -> anyhow::Result<()> {
+            let data = evaluate("\"5 a, 12 b, -3 c\" ** 2 clamp 0 20")?;
+            assert_eq!(data, "10 a, 20 b, 0 c");
+            Ok(())
+        }
+
+        #[test]
+        fn clamp_index() -> anyhow::Result<()> {
+            let data = evaluate("\"5 a, 12 b, -3 c\" clamp[-1] 0 1 clamp[1] -1.5 7.5")?;
+            assert_eq!(data, "5 a, 7.5 b, 0 c");
+            Ok(())
+        }
+
+        #[test]
+        fn clamp_wrong_bounds_is_err() {
+            let res = evaluate("\"5 a\" clamp 10 1");
+            assert!(matches!(res, Err(EvalError::InvalidBounds(_, _))));
+        }
+
+        #[test]
+        fn clamp_nan_bounds_is_err() -> anyhow::Result<()> {
+            use string_mult::evaluating::evaluate_command;
+            use string_mult::parsing::parse_command;
+            use string_mult::OperationType;
+
+            let mut command = parse_command("\"5 a\" clamp 1 10")?;
+            command.operations[0].operation_type = OperationType::Clamp(None, f64::NAN);
+            let res = evaluate_command(&command);
+            assert!(matches!(res, Err(EvalError::InvalidBounds(low, _)) if low.is_nan()));
+            command.operations[0].operation_type = OperationType::Clamp(None, 1.0);
+            command.operations[0].argument = either::Either::Right(f64::NAN);
+            let res = evaluate_command(&command);
+            assert!(matches!(res, Err(EvalError::InvalidBounds(_, high)) if high.is_nan()));
+            Ok(())
+        }
+
+        #[test]
+        fn min_and_max() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"5, 12, -3\" ** 3 min 20")?, "15, 20, -9");
+            assert_eq!(evaluate("\"5, 12, -3\" max 0 *[2] 2")?, "5, 12, 0");
+            assert_eq!(evaluate("\"5, 12, -3\" min[1] 10 max[0] 6")?, "6, 10, -3");
+            Ok(())
+        }
+
+        #[test]
+        fn bound_index_out_of_range() {
+            let res = evaluate("\"5, 12\" min[2] 10");
+            assert!(matches!(res, Err(EvalError::IndexOutOfRange(2, 2))));
+        }
+    }
+
     mod duplication {
         use super::*;
 
@@ -73,6 +193,90 @@ mod evaluation {
             Ok(())
         }
 
+        #[test]
+        fn duplicate_with_separator() -> anyhow::Result<()> {
+            let data = evaluate("\"a\" *** 3 sep \", \"")?;
+            assert_eq!(data, "a, a, a");
+            Ok(())
+        }
+
+        #[test]
+        fn duplicate_negative_with_separator() -> anyhow::Result<()> {
+            let data = evaluate("\"12\" ***-2\tsep\";\" ** 2")?;
+            assert_eq!(data, "42;42");
+            Ok(())
+        }
+
+        #[test]
+        fn separator_numbers_are_text() -> anyhow::Result<()> {
+            let data = evaluate("\"1\" *** 2 sep \" 2 \" *[1] 5 *** 2")?;
+            assert_eq!(data, "1 2 51 2 5");
+            Ok(())
+        }
+
+        #[test]
+        fn chained_duplications_keep_separators() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"a\" *** 2 sep \",\" *** 2")?, "a,aa,a");
+            assert_eq!(evaluate("\"1a\" *** 2 sep \",\" *** 2")?, "1a,1a1a,1a");
+            assert_eq!(
+                evaluate("\"a\" *** 2 sep \",\" *** 2 sep \";\"")?,
+                "a,a;a,a"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn duplicate_with_step() -> anyhow::Result<()> {
+            let data = evaluate("\"Box 1; \" *** 3 step 1")?;
+            assert_eq!(data, "Box 1; Box 2; Box 3; ");
+            Ok(())
+        }
+
+        #[test]
+        fn duplicate_with_indexed_steps() -> anyhow::Result<()> {
+            let data = evaluate("\"Box 1 of 5kg\" *** 3 sep \", \" step[0] 1 scale[-1] 2")?;
+            assert_eq!(data, "Box 1 of 5kg, Box 2 of 10kg, Box 3 of 20kg");
+            Ok(())
+        }
+
+        #[test]
+        fn duplicate_step_out_of_range() {
+            let res = evaluate("\"Box 1\" *** 3 step[1] 1");
+            assert!(res.is_err());
+        }
+
+        #[test]
+        fn duplicate_negative_reverse_modes() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"-12.5x\" *** -1")?, "x5.21-");
+            assert_eq!(evaluate("\"-12.5x\" *** -1 rev chars")?, "x5.21-");
+            assert_eq!(
+                evaluate("\"-12.5x, 3y\" *** -1 rev pieces * 2")?,
+                "y6 ,x-12.5"
+            );
+            assert_eq!(
+                evaluate("\"a🇺🇦 e\u{301}1\" *** -2 rev graphemes")?,
+                "1e\u{301} 🇺🇦a1e\u{301} 🇺🇦a"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn duplicate_reverse_mode_default() -> anyhow::Result<()> {
+            use string_mult::evaluating::{evaluate_with, EvalContext};
+            use string_mult::ReverseMode;
+
+            let context = EvalContext {
+                reverse_mode: ReverseMode::Pieces,
+                ..Default::default()
+            };
+            assert_eq!(evaluate_with("\"-12.5x\" *** -1", &context)?, "x-12.5");
+            assert_eq!(
+                evaluate_with("\"-12.5x\" *** -1 rev chars", &context)?,
+                "x5.21-"
+            );
+            Ok(())
+        }
+
         #[test]
         fn duplicate_zero() -> anyhow::Result<()> {
             let data = evaluate("\"123abcde\"   \t\t***0")?;
@@ -81,6 +285,346 @@ mod evaluation {
         }
     }
 
+    mod integer_policy {
+        use super::*;
+        use string_mult::evaluating::{evaluate_with, EvalContext, EvalError, IntegerPolicy};
+
+        fn context(integer_policy: IntegerPolicy) -> EvalContext {
+            EvalContext {
+                integer_policy,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn float_is_default() -> anyhow::Result<()> {
+            let data = evaluate("\"12 packs\" ** 0.3")?;
+            assert_eq!(data, "3.6 packs");
+            Ok(())
+        }
+
+        #[test]
+        fn rounding_policies() -> anyhow::Result<()> {
+            let input = "\"12 packs, 2.5 l\" ** 0.3";
+            let round = evaluate_with(input, &context(IntegerPolicy::Round))?;
+            assert_eq!(round, "4 packs, 0.75 l");
+            let floor = evaluate_with(input, &context(IntegerPolicy::Floor))?;
+            assert_eq!(floor, "3 packs, 0.75 l");
+            let ceil = evaluate_with(input, &context(IntegerPolicy::Ceil))?;
+            assert_eq!(ceil, "4 packs, 0.75 l");
+            let truncate = evaluate_with("\"-12 packs\" * 0.3", &context(IntegerPolicy::Truncate))?;
+            assert_eq!(truncate, "-3 packs");
+            Ok(())
+        }
+
+        #[test]
+        fn reject_points_at_number() {
+            let res = evaluate_with(
+                "\"1.5 l, 12 packs\" ** 0.3",
+                &context(IntegerPolicy::Reject),
+            );
+            assert!(matches!(res, Err(EvalError::NonIntegralResult(2))));
+        }
+
+        #[test]
+        fn reject_allows_integral_results() -> anyhow::Result<()> {
+            let data = evaluate_with("\"12 packs\" ** 0.5", &context(IntegerPolicy::Reject))?;
+            assert_eq!(data, "6 packs");
+            Ok(())
+        }
+
+        #[test]
+        fn decimal_literals_are_not_integers() -> anyhow::Result<()> {
+            let data = evaluate_with("\"12.0 packs\" ** 0.3", &context(IntegerPolicy::Reject))?;
+            assert_eq!(data, "3.6 packs");
+            let data = evaluate_with("\"2.5 packs\" ** 2 ** 0.3", &context(IntegerPolicy::Reject))?;
+            assert_eq!(data, "1.5 packs");
+            Ok(())
+        }
+
+        #[test]
+        fn fractional_results_stay_fractional() -> anyhow::Result<()> {
+            let data = evaluate_with("\"3 packs\" ** 0.5 ** 2", &context(IntegerPolicy::Floor))?;
+            assert_eq!(data, "2 packs");
+            let data = evaluate_with("\"3 packs\" ** 0.5 ** 4", &context(IntegerPolicy::Float))?;
+            assert_eq!(data, "6 packs");
+            Ok(())
+        }
+    }
+
+    mod safeguards {
+        use super::*;
+        use string_mult::evaluating::{evaluate_with, EvalContext, EvalError};
+
+        #[test]
+        fn infinity_is_err() {
+            let res = evaluate(&format!(
+                "\"x {} 3\" ** {}",
+                "9".repeat(300),
+                "9".repeat(300)
+            ));
+            assert!(matches!(res, Err(EvalError::NonFiniteResult(1))));
+        }
+
+        #[test]
+        fn precision_loss_is_err() {
+            let res = evaluate("\"x 9007199254740992\" ** 2");
+            assert!(matches!(res, Err(EvalError::PrecisionLoss(1))));
+        }
+
+        #[test]
+        fn error_points_at_expanded_piece() {
+            let res = evaluate("\"x 9007199254740991\" *** 2 step 9007199254740991");
+            assert!(matches!(res, Err(EvalError::PrecisionLoss(3))));
+        }
+
+        #[test]
+        fn exact_values_are_ok() -> anyhow::Result<()> {
+            let data = evaluate("\"x 4503599627370496\" ** 2")?;
+            assert_eq!(data, "x 9007199254740992");
+            Ok(())
+        }
+
+        #[test]
+        fn safeguards_can_be_disabled() -> anyhow::Result<()> {
+            let context = EvalContext {
+                allow_non_finite: true,
+                allow_precision_loss: true,
+                ..Default::default()
+            };
+            let data = evaluate_with(
+                &format!("\"{}\" * {}", "9".repeat(300), "9".repeat(300)),
+                &context,
+            )?;
+            assert_eq!(data, "inf");
+            let data = evaluate_with("\"x 9007199254740992\" ** 2", &context)?;
+            assert_eq!(data, "x 18014398509481984");
+            Ok(())
+        }
+    }
+
+    mod limits {
+        use string_mult::evaluating::{evaluate_with, EvalContext, EvalError, Limit, Limits};
+
+        fn context(limits: Limits) -> EvalContext {
+            EvalContext {
+                limits,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn huge_duplication_is_err() {
+            let context = context(Limits {
+                max_output_bytes: Some(1 << 20),
+                ..Default::default()
+            });
+            let res = evaluate_with("\"x\" *** 1000000000", &context);
+            assert!(matches!(
+                res,
+                Err(EvalError::LimitExceeded(Limit::OutputBytes, 1048576))
+            ));
+        }
+
+        #[test]
+        fn pieces_limit() -> anyhow::Result<()> {
+            let context = context(Limits {
+                max_pieces: Some(6),
+                ..Default::default()
+            });
+            assert_eq!(evaluate_with("\"1a\" *** 3", &context)?, "1a1a1a");
+            let res = evaluate_with("\"1a\" *** 3 *** -2", &context);
+            assert!(matches!(
+                res,
+                Err(EvalError::LimitExceeded(Limit::Pieces, 6))
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn operations_limit() {
+            let context = context(Limits {
+                max_operations: Some(2),
+                ..Default::default()
+            });
+            assert!(evaluate_with("\"1a\" ** 2 *** 2", &context).is_ok());
+            let res = evaluate_with("\"1a\" ** 2 *** 2 * 3", &context);
+            assert!(matches!(
+                res,
+                Err(EvalError::LimitExceeded(Limit::Operations, 2))
+            ));
+        }
+
+        #[test]
+        fn steps_are_limited() -> anyhow::Result<()> {
+            let context = context(Limits {
+                max_output_bytes: Some(100),
+                ..Default::default()
+            });
+            assert_eq!(
+                evaluate_with("\"x 1\" *** 3 step 1", &context)?,
+                "x 1x 2x 3"
+            );
+            let res = evaluate_with("\"x 1\" *** 10 step 100000000000", &context);
+            assert!(matches!(
+                res,
+                Err(EvalError::LimitExceeded(Limit::OutputBytes, 100))
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn case_mapping_is_limited() -> anyhow::Result<()> {
+            let context = context(Limits {
+                max_output_bytes: Some(100),
+                ..Default::default()
+            });
+            assert_eq!(evaluate_with("\"ΐ\" *** 2 upper", &context)?.len(), 12);
+            for operation in ["upper", "title"] {
+                let res = evaluate_with(&format!("\"ΐ\" *** 50 {operation}"), &context);
+                assert!(matches!(
+                    res,
+                    Err(EvalError::LimitExceeded(Limit::OutputBytes, 100))
+                ));
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn replacement_is_limited() -> anyhow::Result<()> {
+            let context = context(Limits {
+                max_output_bytes: Some(1000),
+                ..Default::default()
+            });
+            let grow = " replace \"a\" \"aaaaaaaaaa\"";
+            let data = evaluate_with(
+                &format!("\"a, 1\" *** 2 sep \"a\"{}", grow.repeat(2)),
+                &context,
+            )?;
+            assert_eq!(data.len(), (100 + 3) * 2 + 100);
+            let res = evaluate_with(&format!("\"a\"{}", grow.repeat(4)), &context);
+            assert!(matches!(
+                res,
+                Err(EvalError::LimitExceeded(Limit::OutputBytes, 1000))
+            ));
+            Ok(())
+        }
+    }
+
+    mod lazy {
+        use string_mult::evaluating::{evaluate_lazy, EvalContext};
+
+        #[test]
+        fn duplication_is_not_expanded() -> anyhow::Result<()> {
+            let data = evaluate_lazy("\"12 packs \" ** 2 *** 500 *** 2", &EvalContext::default())?;
+            assert_eq!(data.params.len(), 2);
+            assert_eq!(data.repeat, 1000);
+            assert_eq!(data.to_string(), "24 packs ".repeat(1000));
+            Ok(())
+        }
+
+        #[test]
+        fn indexed_operation_expands() -> anyhow::Result<()> {
+            let data = evaluate_lazy("\"1a\" *** 3 *[1] 5 ** 2", &EvalContext::default())?;
+            assert_eq!(data.repeat, 1);
+            assert_eq!(data.to_string(), "2a10a2a");
+            Ok(())
+        }
+
+        #[test]
+        fn writes_into_io_and_fmt() -> anyhow::Result<()> {
+            let data = evaluate_lazy("\"-1 \" *** -3", &EvalContext::default())?;
+            let mut bytes = Vec::new();
+            data.write_to(&mut bytes)?;
+            assert_eq!(bytes, b" 1- 1- 1-");
+            let mut text = String::new();
+            std::fmt::Write::write_fmt(&mut text, format_args!("{data}"))?;
+            assert_eq!(text, " 1- 1- 1-");
+            Ok(())
+        }
+    }
+
+    mod text {
+        use super::*;
+        use string_mult::evaluating::EvalError;
+
+        #[test]
+        fn case_operators_keep_numbers() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"12 packs, 3ml\" ** 2 upper")?, "24 PACKS, 6ML");
+            assert_eq!(
+                evaluate("\"12 PACKS\" *** 2 sep \" AND \" lower")?,
+                "12 packs and 12 packs"
+            );
+            assert_eq!(
+                evaluate("\"hELLO wORLD-wide 3rd\" title")?,
+                "Hello World-Wide 3rd"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn trim_and_pad() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"  12 packs \" trim")?, "12 packs");
+            assert_eq!(evaluate("\"12 packs\" ** 2 pad 10")?, "24 packs  ");
+            assert_eq!(evaluate("\"12\" pad -4 *** 2")?, "  12  12");
+            assert_eq!(evaluate("\"é1\" pad 3")?, "é1 ");
+            assert_eq!(evaluate("\"12 packs\" pad 3")?, "12 packs");
+            Ok(())
+        }
+
+        #[test]
+        fn replace() -> anyhow::Result<()> {
+            let data = evaluate("\"12 packs\" replace \"packs\" \"boxes of 2\" ** 2")?;
+            assert_eq!(data, "24 boxes of 2");
+            assert!(matches!(
+                evaluate("\"12 packs\" replace \"\" \"a\""),
+                Err(EvalError::EmptyReplacement)
+            ));
+            Ok(())
+        }
+    }
+
+    mod unicode {
+        use super::*;
+        use string_mult::evaluating::{evaluate_with, EvalContext};
+
+        #[test]
+        fn default_reversal_keeps_graphemes() -> anyhow::Result<()> {
+            let data = evaluate("\"👩‍👩‍👧 x\u{308} 🇺🇦\" *** -1")?;
+            assert_eq!(data, "🇺🇦 x\u{308} 👩‍👩‍👧");
+            Ok(())
+        }
+
+        #[test]
+        fn digit_with_combining_mark_is_text() -> anyhow::Result<()> {
+            let data = evaluate("\"5\u{301} 1\u{fe0f}\u{20e3} 3\" ** 2")?;
+            assert_eq!(data, "5\u{301} 1\u{fe0f}\u{20e3} 6");
+            Ok(())
+        }
+
+        #[test]
+        fn unicode_digits() -> anyhow::Result<()> {
+            assert_eq!(evaluate("\"١٢ packs\" ** 2")?, "24 packs");
+            assert_eq!(evaluate("\"１２.５ l, ३ kg\" ** 2")?, "25 l, 6 kg");
+            Ok(())
+        }
+
+        #[test]
+        fn native_digits_output() -> anyhow::Result<()> {
+            let context = EvalContext {
+                native_digits: true,
+                ..Default::default()
+            };
+            assert_eq!(evaluate_with("\"١٢ packs\" ** 2.5", &context)?, "٣٠ packs");
+            assert_eq!(
+                evaluate_with("\"१२, 3\" *** 2 sep \"; \"", &context)?,
+                "१२, ३; १२, ३"
+            );
+            assert_eq!(evaluate_with("\"12 packs\" ** 2", &context)?, "24 packs");
+            Ok(())
+        }
+    }
+
     #[test]
     fn two_operations() -> anyhow::Result<()> {
         let data = evaluate("\"123abcdef\"   \t\t***2  *[1] 2")?;
@@ -119,4 +663,139 @@ mod evaluation {
         assert_eq!(results[2].as_ref().unwrap(), "57 bottles.");
         Ok(())
     }
+
+    #[test]
+    fn command_list_expands_macros() -> anyhow::Result<()> {
+        let data =
+            "macro double_label = *[0] 2 *** 3 sep \",\"\n\"x 1\" @double_label\n\"y 2\" @oops";
+        let results = evaluate_list(data)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), "x 2,x 2,x 2");
+        assert!(results[1].is_err());
+        Ok(())
+    }
+
+    mod functions {
+        use super::*;
+        use string_mult::evaluating::{evaluate_command_with, EvalContext, EvalError};
+        use string_mult::parsing::{parse_command, parse_functions};
+
+        #[test]
+        fn call_substitutes_arguments() -> anyhow::Result<()> {
+            let data = "fn scale(f) = ** f *** 2\n\"3 cups\" @scale(1.5)\n\"2 cups\" @scale(-1)";
+            let results = evaluate_list(data)?;
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].as_ref().unwrap(), "4.5 cups4.5 cups");
+            assert_eq!(results[1].as_ref().unwrap(), "-2 cups-2 cups");
+            Ok(())
+        }
+
+        #[test]
+        fn integer_parameters() -> anyhow::Result<()> {
+            let data =
+                "fn rep(n, w) = *** n sep \",\" pad w\n\"a 1\" @rep(2, 9)\n\"a 1\" @rep(1.5, 9)";
+            let results = evaluate_list(data)?;
+            assert_eq!(results[0].as_ref().unwrap(), "a 1,a 1  ");
+            assert!(matches!(results[1], Err(EvalError::DuplicatingByFloat)));
+            Ok(())
+        }
+
+        #[test]
+        fn functions_from_context() -> anyhow::Result<()> {
+            let context = EvalContext {
+                functions: parse_functions("fn half() = ** 0.5\nfn twice(f) = @half() ** f ** f")?,
+                ..Default::default()
+            };
+            let command = parse_command("\"8 cups\" @twice(3)")?;
+            assert_eq!(evaluate_command_with(&command, &context)?, "36 cups");
+            Ok(())
+        }
+
+        #[test]
+        fn wrong_calls() -> anyhow::Result<()> {
+            let data = "fn f(a) = ** a\nfn g() = @g()\n\"x 1\" @f()\n\"x 1\" @h(1)\n\"x 1\" @g()";
+            let results = evaluate_list(data)?;
+            assert!(
+                matches!(&results[0], Err(EvalError::ArgumentCount(name, 1, 0)) if name == "f")
+            );
+            assert!(matches!(&results[1], Err(EvalError::UnknownFunction(name)) if name == "h"));
+            assert!(matches!(&results[2], Err(EvalError::RecursiveFunction(name)) if name == "g"));
+            Ok(())
+        }
+
+        #[test]
+        fn duplication_by_zero_stops_command() -> anyhow::Result<()> {
+            let results = evaluate_list("fn none() = *** 0\n\"x 1\" @none() *[0] 2")?;
+            assert_eq!(results[0].as_ref().unwrap(), "");
+            Ok(())
+        }
+    }
+
+    mod tracing {
+        use string_mult::evaluating::{
+            trace_command, trace_list, EvalContext, EvalError, MAX_TRACE_OUTPUT_BYTES,
+        };
+        use string_mult::parsing::parse_command;
+
+        #[test]
+        fn steps_record_outputs_and_changes() -> anyhow::Result<()> {
+            let command = parse_command("\"3 cups and 4 spoons\" *[1] 2 *** 2")?;
+            let trace = trace_command(&command, &EvalContext::default());
+            assert_eq!(trace.initial, "3 cups and 4 spoons");
+            assert_eq!(trace.steps.len(), 2);
+            assert_eq!(trace.steps[0].operation, command.operations[0]);
+            assert_eq!(trace.steps[0].output, "3 cups and 8 spoons");
+            assert_eq!(trace.steps[0].changed, vec![2]);
+            assert_eq!(trace.steps[1].changed, Vec::<usize>::new());
+            assert_eq!(trace.steps[1].repeat, 2);
+            assert_eq!(
+                trace.result?.to_string(),
+                "3 cups and 8 spoons3 cups and 8 spoons"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn error_keeps_previous_steps() -> anyhow::Result<()> {
+            let command = parse_command("\"x 1\" ** 2 *[3] 2 ** 2")?;
+            let trace = trace_command(&command, &EvalContext::default());
+            assert_eq!(trace.steps.len(), 1);
+            assert_eq!(trace.steps[0].output, "x 2");
+            assert!(matches!(
+                trace.result,
+                Err(EvalError::IndexOutOfRange(3, 1))
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn calls_precede_their_operations() -> anyhow::Result<()> {
+            let traces = trace_list(
+                "fn twice(n) = ** n *** 2\n\"a 2\" @twice(3)",
+                &EvalContext::default(),
+            )?;
+            let steps = &traces[0].1.steps;
+            let depths: Vec<usize> = steps.iter().map(|s| s.depth).collect();
+            assert_eq!(depths, vec![0, 1, 1]);
+            assert_eq!(steps[0].output, "a 6a 6");
+            assert_eq!(steps[0].changed, vec![1]);
+            assert_eq!(steps[1].output, "a 6");
+            Ok(())
+        }
+
+        #[test]
+        fn large_outputs_are_cut() -> anyhow::Result<()> {
+            let command = parse_command("\"x\" *** 100000 *** 100000")?;
+            let trace = trace_command(&command, &EvalContext::default());
+            for step in &trace.steps {
+                assert!(step.truncated);
+                assert_eq!(step.output.len(), MAX_TRACE_OUTPUT_BYTES);
+            }
+            assert_eq!(trace.steps[1].repeat, 10_000_000_000);
+            assert_eq!(trace.result?.repeat, 10_000_000_000);
+            let trace = trace_command(&parse_command("\"x\" *** 3")?, &EvalContext::default());
+            assert!(!trace.steps[0].truncated);
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,81 @@
+mod formatting {
+    use string_mult::formatting::*;
+    use string_mult::parsing::parse_command;
+    use string_mult::printing::print;
+
+    #[test]
+    fn normalizes_spacing() -> anyhow::Result<()> {
+        let res = format_list(
+            "  \"12 packs\"   ** 2\t*[0]  3\nmacro   m =  *** 2  sep \",\"  rev pieces\n\
+             fn f( a ,b )=** a  ^^b clamp[1]  0 10\n\"x 1\"  @f( 1 , 2 )  @m\n",
+        )?;
+        assert_eq!(
+            res,
+            "\"12 packs\" ** 2 *[0] 3\nmacro m = *** 2 sep \",\" rev pieces\n\
+             fn f(a, b) = ** a ^^ b clamp[1] 0 10\n\"x 1\" @f(1, 2) @m\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn matches_printing() -> anyhow::Result<()> {
+        let commands = [
+            "\"x 1.50\" * 2.50 ^ 3 /[1] 02",
+            "\"a \\\"1\\\"\" replace \"\\\"\" \"'\" *** 2 sep \";\" scale 2.0",
+            "\"١٢ x\" ** 2 where in 1..3 or ==4 % 5",
+        ];
+        for command in commands {
+            assert_eq!(format_list(command)?, print(&parse_command(command)?));
+        }
+        let res = format_list("fn f(a, b) = * a  pad b  @f(1.0)\n\"x 1\" @f( 2.50 , 1 )")?;
+        assert_eq!(res, "fn f(a, b) = *[0] a pad b @f(1)\n\"x 1\" @f(2.5, 1)");
+        Ok(())
+    }
+
+    #[test]
+    fn normalizes_predicates() -> anyhow::Result<()> {
+        let res = format_list("\"y 2\" **2 where not>1 and ( <3 or   in 1..=2)")?;
+        assert_eq!(res, "\"y 2\" ** 2 where not > 1 and (< 3 or in 1..=2)");
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_comments_and_wrong_commands() -> anyhow::Result<()> {
+        let res = format_list(
+            "# prices  \n\"x 1\"  **  2   #  double \n\n  garbage  here\t\n\"y\"  upper",
+        )?;
+        assert_eq!(
+            res,
+            "# prices  \n\"x 1\" ** 2 #  double \n\n  garbage  here\n\"y\" upper"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_commands_are_idempotent() -> anyhow::Result<()> {
+        for input in ["bad first\n   \"x\" ** 2", "bad \"x\" ** 2 \t\n"] {
+            let res = format_list(input)?;
+            assert_eq!(format_list(&res)?, res);
+        }
+        assert_eq!(
+            format_list("bad first\n   \"x\" ** 2")?,
+            "bad first\n\"x\" ** 2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_line_breaks() -> anyhow::Result<()> {
+        let res = format_list("\"x 1\" ** 2 \r\n\r\n\"y\" *** 2\t\r\n")?;
+        assert_eq!(res, "\"x 1\" ** 2\r\n\r\n\"y\" *** 2\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn formatting_is_idempotent() -> anyhow::Result<()> {
+        let res = format_list("macro m = ** 2 \n \"x 1\" @m  *** 3 sep \"\\\"\" # note\n")?;
+        assert!(is_formatted(&res)?);
+        assert!(!is_formatted("\"x 1\"  ** 2")?);
+        Ok(())
+    }
+}
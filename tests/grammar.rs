@@ -26,6 +26,23 @@ mod grammar {
         Ok(())
     }
 
+    #[test]
+    fn num_in_other_scripts() -> anyhow::Result<()> {
+        for input in ["-١٢.٥", "۱۲", "१२", "১২", "๑๒", "１２"] {
+            let data = StringMultGrammar::parse(Rule::num, input)?
+                .next()
+                .ok_or(anyhow::anyhow!("no field"))?;
+            assert_eq!(data.as_str(), input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn num_followed_by_mark_is_err() {
+        let data = StringMultGrammar::parse(Rule::num, "12\u{301}");
+        assert!(data.is_err());
+    }
+
     #[test]
     fn wrong_num_is_err() -> anyhow::Result<()> {
         let data = StringMultGrammar::parse(Rule::num, "--4.- 4.43 -15");
@@ -85,6 +102,32 @@ mod grammar {
         Ok(())
     }
 
+    #[test]
+    fn arithmetic_operators() -> anyhow::Result<()> {
+        let data =
+            StringMultGrammar::parse(Rule::command, "\"a\" ^[1] 2 ^^ 3 % 4 %% 5 /[-1] 6 // 7")?
+                .next()
+                .ok_or(anyhow::anyhow!("no command"))?;
+        let rules: Vec<_> = data
+            .into_inner()
+            .filter(|p| p.as_rule() != Rule::num)
+            .map(|p| p.as_rule())
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::str_param,
+                Rule::pow,
+                Rule::powAll,
+                Rule::modulo,
+                Rule::moduloAll,
+                Rule::intDiv,
+                Rule::intDivAll
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn duplicate() -> anyhow::Result<()> {
         let data = StringMultGrammar::parse(Rule::duplicate, "***")?
@@ -96,6 +139,104 @@ mod grammar {
         Ok(())
     }
 
+    #[test]
+    fn separator() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::separator, "sep \t\", \"")?
+            .next()
+            .ok_or(anyhow::anyhow!("no separator"))?;
+        print!("{:?}", data);
+        assert_eq!(data.as_str(), "sep \t\", \"");
+        assert_eq!(data.into_inner().next().unwrap().as_rule(), Rule::str_param);
+        Ok(())
+    }
+
+    #[test]
+    fn separator_only_follows_duplicate() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::command, "\"a\" ** 3 sep \",\"")?
+            .next()
+            .ok_or(anyhow::anyhow!("no command"))?;
+        assert_eq!(data.as_str(), "\"a\" ** 3");
+        Ok(())
+    }
+
+    #[test]
+    fn step() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::step, "scale[-1]\t2.5")?
+            .next()
+            .ok_or(anyhow::anyhow!("no step"))?;
+        print!("{:?}", data);
+        let mut inner_pairs = data.into_inner();
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "scale");
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "-1");
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "2.5");
+        Ok(())
+    }
+
+    #[test]
+    fn replace() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::replace, "replace \"a\"\t\"b c\"")?
+            .next()
+            .ok_or(anyhow::anyhow!("no replace"))?;
+        print!("{:?}", data);
+        let mut inner_pairs = data.into_inner();
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "\"a\"");
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "\"b c\"");
+        Ok(())
+    }
+
+    #[test]
+    fn command_allows_text_operators() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::command, "\"str\" upper pad 3 ** 2 trim")?
+            .next()
+            .ok_or(anyhow::anyhow!("no command"))?;
+        let rules: Vec<_> = data.into_inner().map(|p| p.as_rule()).collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::str_param,
+                Rule::upper,
+                Rule::pad,
+                Rule::int,
+                Rule::multAll,
+                Rule::num,
+                Rule::trim
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::filter, "where in 1..100 or (< -5 and != -7)")?
+            .next()
+            .ok_or(anyhow::anyhow!("no filter"))?;
+        print!("{:#?}", data);
+        assert_eq!(data.as_str(), "where in 1..100 or (< -5 and != -7)");
+        Ok(())
+    }
+
+    #[test]
+    fn filter_only_follows_mult_all() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::command, "\"a\" * 3 where > 1")?
+            .next()
+            .ok_or(anyhow::anyhow!("no command"))?;
+        assert_eq!(data.as_str(), "\"a\" * 3");
+        Ok(())
+    }
+
+    #[test]
+    fn clamp() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::clamp, "clamp[-2]\t-1.5 3")?
+            .next()
+            .ok_or(anyhow::anyhow!("no clamp"))?;
+        print!("{:?}", data);
+        assert_eq!(data.as_str(), "clamp[-2]\t-1.5");
+        let mut inner_pairs = data.into_inner();
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "-2");
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "-1.5");
+        Ok(())
+    }
+
     #[test]
     fn inner_str_text() -> anyhow::Result<()> {
         let data = StringMultGrammar::parse(Rule::inner_str_text, " abc -4def,--5\"")?
@@ -121,6 +262,19 @@ mod grammar {
         Ok(())
     }
 
+    #[test]
+    fn str_param_allows_escapes() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::str_param, r#""a \"b\" \\" 2"#)?
+            .next()
+            .ok_or(anyhow::anyhow!("no field"))?;
+        print!("{:#?}", data);
+        assert_eq!(data.as_str(), r#""a \"b\" \\""#);
+        let mut inner_pairs = data.into_inner();
+        assert_eq!(inner_pairs.next().unwrap().as_str(), r#"a \"b\" \\"#);
+        assert!(inner_pairs.next().is_none());
+        Ok(())
+    }
+
     #[test]
     fn str_param_allows_numbers_abscence() -> anyhow::Result<()> {
         let data = StringMultGrammar::parse(Rule::str_param, "\"abc def\"")?
@@ -178,6 +332,70 @@ mod grammar {
         Ok(())
     }
 
+    #[test]
+    fn macro_def() -> anyhow::Result<()> {
+        let data =
+            StringMultGrammar::parse(Rule::macro_def, "macro double_label = *[0] 2 @other\n")?
+                .next()
+                .ok_or(anyhow::anyhow!("no macro"))?;
+        print!("{:#?}", data);
+        assert_eq!(data.as_str(), "macro double_label = *[0] 2 @other");
+        let mut inner_pairs = data.into_inner();
+        assert_eq!(inner_pairs.next().unwrap().as_str(), "double_label");
+        let body = inner_pairs.next().unwrap();
+        assert_eq!(body.as_rule(), Rule::operations);
+        let rules: Vec<_> = body.into_inner().map(|p| p.as_rule()).collect();
+        assert_eq!(rules, [Rule::mult, Rule::num, Rule::macro_call]);
+        Ok(())
+    }
+
+    #[test]
+    fn fn_def() -> anyhow::Result<()> {
+        let data = StringMultGrammar::parse(Rule::fn_def, "fn scale(f, n) = ** f *** n @m(2)")?
+            .next()
+            .ok_or(anyhow::anyhow!("no function"))?;
+        print!("{:#?}", data);
+        let rules: Vec<_> = data.clone().into_inner().map(|p| p.as_rule()).collect();
+        assert_eq!(
+            rules,
+            [Rule::ident, Rule::ident, Rule::ident, Rule::operations]
+        );
+        let body = data.into_inner().last().unwrap();
+        let rules: Vec<_> = body.into_inner().map(|p| p.as_rule()).collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::multAll,
+                Rule::param_ref,
+                Rule::duplicate,
+                Rule::param_ref,
+                Rule::fn_call
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn comments_in_list() -> anyhow::Result<()> {
+        let data =
+            StringMultGrammar::parse(Rule::commands_list, "# a\n\"x 1\" ** 2 # b \"c\" ** 3\n")?
+                .next()
+                .ok_or(anyhow::anyhow!("no list"))?;
+        let parts: Vec<_> = data
+            .into_inner()
+            .map(|p| (p.as_rule(), p.as_str()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (Rule::comment, "# a"),
+                (Rule::command, "\"x 1\" ** 2"),
+                (Rule::comment, "# b \"c\" ** 3")
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn wrong_command_in_list() -> anyhow::Result<()> {
         let data = StringMultGrammar::parse(
@@ -0,0 +1,101 @@
+use string_mult::evaluating::{evaluate_command, EvalContext};
+use string_mult::inverting::*;
+use string_mult::parsing::{parse_command, parse_functions};
+use string_mult::StringMultCommand;
+
+mod inverting {
+    use super::*;
+
+    fn round_trip(input: &str) -> anyhow::Result<Vec<String>> {
+        let command = parse_command(input)?;
+        let steps = invert(&command)?;
+        let output = evaluate_command(&command)?;
+        let original = StringMultCommand {
+            operations: Vec::new(),
+            ..command
+        };
+        assert_eq!(undo(&output, &steps)?, evaluate_command(&original)?);
+        Ok(steps.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn reciprocal_multipliers() -> anyhow::Result<()> {
+        assert_eq!(
+            round_trip("\"3 cups and 4 spoons\" ** 2 *[1] 4")?,
+            vec!["*[1] 0.25", "** 0.5"]
+        );
+        assert_eq!(round_trip("\"9 a\" ^[0] 2")?, vec!["^[0] 0.5"]);
+        Ok(())
+    }
+
+    #[test]
+    fn deduplication() -> anyhow::Result<()> {
+        assert_eq!(
+            round_trip("\"1 a\" *** 3 sep \", \" *[1] 2")?,
+            vec!["*[1] 0.5", "dedup 3 sep \", \""]
+        );
+        assert_eq!(
+            round_trip("\"12 ab\" *** -2")?,
+            vec!["dedup 2", "*** -1 rev graphemes"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_requires_exact_repetition() {
+        let steps = [UndoStep::Deduplicate(2, String::new())];
+        assert_eq!(undo("ab ab ", &steps).unwrap(), "ab ");
+        assert!(matches!(
+            undo("ab ac ", &steps),
+            Err(InvertError::NotRepeated(2))
+        ));
+    }
+
+    #[test]
+    fn called_functions() -> anyhow::Result<()> {
+        let context = EvalContext {
+            functions: parse_functions("fn scale(f) = ** f *** 2")?,
+            ..Default::default()
+        };
+        let command = parse_command("\"3 cups\" @scale(4)")?;
+        let steps = invert_with(&command, &context)?;
+        assert_eq!(steps.len(), 2);
+        assert_eq!(undo_with("12 cups12 cups", &steps, &context)?, "3 cups");
+        Ok(())
+    }
+
+    #[test]
+    fn irreversible_operations() -> anyhow::Result<()> {
+        let error = |input: &str| invert(&parse_command(input).unwrap()).unwrap_err();
+        assert!(matches!(
+            error("\"1 a\" ** 2 *** 0"),
+            InvertError::DiscardedOutput(1)
+        ));
+        assert!(matches!(
+            error("\"1 a\" * 0"),
+            InvertError::MultiplicationByZero(0)
+        ));
+        assert!(matches!(
+            error("\"1 a\" upper"),
+            InvertError::LossyOperation(0)
+        ));
+        assert!(matches!(
+            error("\"1 a\" clamp 0 5"),
+            InvertError::LossyOperation(0)
+        ));
+        assert!(matches!(
+            error("\"1 a\" ** 2 where > 3"),
+            InvertError::ConditionalOperation(0)
+        ));
+        assert!(matches!(
+            error("\"1 a\" *** 2 step 1"),
+            InvertError::UnequalRepetitions(0)
+        ));
+        assert!(matches!(error("\"-3 a\" ^ 2"), InvertError::NotRestored(s) if s == "3 a"));
+        assert!(matches!(
+            error("\"1\" *** 2 *[1] 3"),
+            InvertError::EvalError(_)
+        ));
+        Ok(())
+    }
+}
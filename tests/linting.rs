@@ -0,0 +1,103 @@
+mod linting {
+    use string_mult::linting::*;
+    use string_mult::parsing::parse_command;
+
+    fn lints(input: &str) -> anyhow::Result<Vec<(Lint, usize)>> {
+        let command = parse_command(input)?;
+        Ok(lint_command(&command)
+            .into_iter()
+            .map(|w| (w.lint, w.operation))
+            .collect())
+    }
+
+    #[test]
+    fn no_op_operations() -> anyhow::Result<()> {
+        assert_eq!(
+            lints("\"x 1\" ** 1 *[0] 1.0 *** 1 ^^ 2")?,
+            [
+                (Lint::NoOpMultiplication, 0),
+                (Lint::NoOpMultiplication, 1),
+                (Lint::NoOpDuplication, 2)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multiplication_by_zero() -> anyhow::Result<()> {
+        assert_eq!(
+            lints("\"x 1\" ** 0 * 0")?,
+            [
+                (Lint::MultiplicationByZero, 0),
+                (Lint::MultiplicationByZero, 1)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn index_out_of_range() -> anyhow::Result<()> {
+        assert_eq!(lints("\"abc\" *[0] 2")?, [(Lint::IndexOutOfRange, 0)]);
+        assert_eq!(
+            lints("\"1 a 2\" *[-2] 2 *[2] 2 *** 2 *[3] 2 ^[-5] 2")?,
+            [(Lint::IndexOutOfRange, 1), (Lint::IndexOutOfRange, 4)]
+        );
+        assert!(lints("\"1 a\" *** -2 rev chars *[5] 2")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn ignored_operations() -> anyhow::Result<()> {
+        assert_eq!(
+            lints("\"x 1\" *** 0 ** 1 upper")?,
+            [(Lint::IgnoredOperations, 0)]
+        );
+        assert!(lints("\"x 1\" ** 2 *** 0")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn codes() {
+        for lint in Lint::ALL {
+            assert_eq!(Lint::from_code(lint.code()), Some(lint));
+        }
+        assert_eq!(Lint::IndexOutOfRange.code(), "W003");
+    }
+
+    #[test]
+    fn list_with_allowed_warnings() -> anyhow::Result<()> {
+        let data =
+            "\"x 1\" ** 1\n\"abc\" *[0] 1 # allow(W001)\n\"y\" *** 1 # allow(W002, W003)\ngarbage";
+        let warnings = lint_list(data)?;
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|(span, w)| (span.line_col(data).0, w.lint))
+            .collect();
+        assert_eq!(
+            found,
+            [(1, Lint::NoOpMultiplication), (2, Lint::IndexOutOfRange)]
+        );
+        assert_eq!(
+            warnings[0].1.to_string(),
+            "W001 in operation 1: multiplication by 1 has no effect"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn allows_apply_to_the_preceding_command() -> anyhow::Result<()> {
+        let data = "\"# allow(W001) 1\" ** 1\n\"x 1\" ** 1 \"y\" *** 1 # allow(W001, W002)";
+        let found: Vec<_> = lint_list(data)?
+            .iter()
+            .map(|(span, w)| (span.start, w.lint))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0, Lint::NoOpMultiplication),
+                (23, Lint::NoOpMultiplication)
+            ]
+        );
+        Ok(())
+    }
+}
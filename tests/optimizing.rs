@@ -0,0 +1,171 @@
+use proptest::prelude::*;
+use string_mult::evaluating::{
+    evaluate_command, evaluate_command_with, EvalContext, IntegerPolicy,
+};
+use string_mult::optimizing::*;
+use string_mult::parsing::parse_command;
+use string_mult::OperationType;
+
+mod optimizing {
+    use super::*;
+
+    fn types(command: &string_mult::StringMultCommand) -> Vec<&OperationType> {
+        command
+            .operations
+            .iter()
+            .map(|o| &o.operation_type)
+            .collect()
+    }
+
+    #[test]
+    fn folds_multiplications() -> anyhow::Result<()> {
+        let command = parse_command("\"x 3\" ** 2 ** 3 *[0] 0.5 *[0] 4 * 2")?;
+        let optimized = optimize(&command);
+        assert_eq!(
+            types(&optimized),
+            [
+                &OperationType::MultAll,
+                &OperationType::Mult(Some(0)),
+                &OperationType::Mult(Some(0))
+            ]
+        );
+        assert_eq!(optimized.operations[0].argument.right(), Some(6.0));
+        assert_eq!(optimized.operations[2].argument.right(), Some(8.0));
+        assert_eq!(evaluate_command(&optimized)?, "x 72");
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_inexact_multiplications() -> anyhow::Result<()> {
+        let command =
+            parse_command("\"x 3\" ** 3 ** 3 ** 0.5 ** 2 *[0] 2 *[1] 2 ** 2 where > 1 ** 2")?;
+        assert_eq!(optimize(&command), command);
+        Ok(())
+    }
+
+    #[test]
+    fn moves_duplications() -> anyhow::Result<()> {
+        let command = parse_command("\"x 3\" *** 2 sep \",\" ** 2 %% 5 ** 4 *[0] 2 *** 3 ^^ 2")?;
+        let optimized = optimize(&command);
+        assert!(matches!(
+            types(&optimized)[..],
+            [
+                OperationType::MultAll,
+                OperationType::Mod(None),
+                OperationType::MultAll,
+                OperationType::DuplicateWith(_),
+                OperationType::Mult(Some(0)),
+                OperationType::Pow(None),
+                OperationType::Duplicate,
+            ]
+        ));
+        assert_eq!(evaluate_command(&optimized)?, evaluate_command(&command)?);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_duplications_changing_numbers() -> anyhow::Result<()> {
+        for input in [
+            "\"x 3\" *** -2 ** 2",
+            "\"x 3\" *** 2 step 1 ** 2",
+            "\"x 3\" *** 0 ** 2",
+        ] {
+            let command = parse_command(input)?;
+            assert_eq!(optimize(&command), command);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn respects_context() -> anyhow::Result<()> {
+        let command = parse_command("\"x 3\" *** 2 ** 2 ** 2")?;
+        let context = EvalContext {
+            integer_policy: IntegerPolicy::Round,
+            limits: string_mult::evaluating::Limits {
+                max_output_bytes: Some(100),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(optimize_with(&command, &context), command);
+        Ok(())
+    }
+}
+
+fn factor() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(vec![
+        "1", "2", "3", "-2", "0.5", "1.5", "4", "-1", "1024", "0.1", "10", "-0.75", "0", "7",
+        "1000",
+    ])
+}
+
+fn operation() -> impl Strategy<Value = String> {
+    prop_oneof![
+        factor().prop_map(|f| format!("** {f}")),
+        factor().prop_map(|f| format!("** {f}")),
+        (-3isize..3, factor()).prop_map(|(i, f)| format!("*[{i}] {f}")),
+        factor().prop_map(|f| format!("* {f}")),
+        (-2isize..4).prop_map(|k| format!("*** {k}")),
+        (1isize..3).prop_map(|k| format!("*** {k} sep \"1,\"")),
+        (1isize..3).prop_map(|k| format!("*** {k} step 1")),
+        factor().prop_map(|f| format!("** {f} where > 2")),
+        prop::sample::select(vec![
+            "^^ 2",
+            "%% 7",
+            "// 2",
+            "clamp -5 100",
+            "min 50",
+            "max -3",
+            "^[0] 2",
+            "upper",
+            "^^ 0.5",
+        ])
+        .prop_map(str::to_string),
+    ]
+}
+
+fn command() -> impl Strategy<Value = String> {
+    (
+        prop::collection::vec(
+            prop_oneof![
+                (-1000i32..1000).prop_map(|n| n.to_string()),
+                (0u32..100, 0u32..100).prop_map(|(a, b)| format!("{a}.{b}")),
+                Just("9007199254740990".to_string()),
+                Just(" a ".to_string()),
+                Just("b-".to_string()),
+            ],
+            0..5,
+        ),
+        prop::collection::vec(operation(), 1..7),
+    )
+        .prop_map(|(pieces, operations)| {
+            format!("\"{}\" {}", pieces.concat(), operations.join(" "))
+        })
+}
+
+proptest! {
+    #[test]
+    fn optimized_evaluates_the_same(source in command()) {
+        let command = parse_command(&source).unwrap();
+        let optimized = optimize(&command);
+        match evaluate_command(&command) {
+            Ok(result) => prop_assert_eq!(evaluate_command(&optimized).ok(), Some(result)),
+            Err(_) => prop_assert!(evaluate_command(&optimized).is_err()),
+        }
+    }
+
+    #[test]
+    fn optimized_evaluates_the_same_with_policy(source in command()) {
+        let context = EvalContext {
+            integer_policy: IntegerPolicy::Floor,
+            allow_precision_loss: true,
+            ..Default::default()
+        };
+        let command = parse_command(&source).unwrap();
+        let optimized = optimize_with(&command, &context);
+        match evaluate_command_with(&command, &context) {
+            Ok(result) => prop_assert_eq!(evaluate_command_with(&optimized, &context).ok(), Some(result)),
+            Err(_) => prop_assert!(evaluate_command_with(&optimized, &context).is_err()),
+        }
+    }
+}
@@ -38,9 +38,21 @@ mod parsing {
 
         let res = res.unwrap();
         assert_eq!(res.params.len(), 4);
-        assert_eq!(res.params[0], ParamsPiece::Num(15_f64));
+        assert_eq!(
+            res.params[0],
+            ParamsPiece::Num {
+                value: 15_f64,
+                integer: true
+            }
+        );
         assert_eq!(res.params[1], ParamsPiece::Str(" packs, ".to_string()));
-        assert_eq!(res.params[2], ParamsPiece::Num(10_f64));
+        assert_eq!(
+            res.params[2],
+            ParamsPiece::Num {
+                value: 10_f64,
+                integer: true
+            }
+        );
         assert_eq!(res.params[3], ParamsPiece::Str("mg/l".to_string()));
 
         assert_eq!(res.operations.len(), 1);
@@ -52,26 +64,267 @@ mod parsing {
         Ok(())
     }
 
+    #[test]
+    fn parsing_separator() -> anyhow::Result<()> {
+        let res = parse_command("\"a\" *** 3 sep \", \" *** 2")?;
+        assert_eq!(res.operations.len(), 2);
+        assert_eq!(
+            res.operations[0].operation_type,
+            OperationType::DuplicateWith(DuplicateOptions {
+                separator: Some(", ".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(res.operations[0].argument.left(), Some(3));
+        assert_eq!(res.operations[1].operation_type, OperationType::Duplicate);
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_steps() -> anyhow::Result<()> {
+        let res = parse_command("\"a 1 b 2\" *** 3 sep \",\" step 1 scale[-1] 2.5")?;
+        assert_eq!(
+            res.operations[0].operation_type,
+            OperationType::DuplicateWith(DuplicateOptions {
+                separator: Some(",".to_string()),
+                steps: vec![
+                    DuplicateStep {
+                        index: None,
+                        kind: StepKind::Offset,
+                        amount: 1.0,
+                    },
+                    DuplicateStep {
+                        index: Some(-1),
+                        kind: StepKind::Scale,
+                        amount: 2.5,
+                    },
+                ],
+                ..Default::default()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_reverse_mode() -> anyhow::Result<()> {
+        let res = parse_command("\"-12.5x\" *** -1 rev pieces")?;
+        assert_eq!(
+            res.operations[0].operation_type,
+            OperationType::DuplicateWith(DuplicateOptions {
+                reverse: Some(ReverseMode::Pieces),
+                ..Default::default()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_digit_script() -> anyhow::Result<()> {
+        let res = parse_command("\"3 and ٤٥.٦\" ** ٢")?;
+        assert_eq!(
+            res.params[2],
+            ParamsPiece::Num {
+                value: 45.6,
+                integer: false
+            }
+        );
+        assert_eq!(res.operations[0].argument.right(), Some(2_f64));
+        assert_eq!(res.digits, DigitScript::ArabicIndic);
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_text_operations() -> anyhow::Result<()> {
+        let res = parse_command("\"a 1\" upper ** 2 pad -5 replace \"a\" \"b\" trim")?;
+        let types: Vec<_> = res.operations.iter().map(|o| &o.operation_type).collect();
+        assert_eq!(
+            types,
+            [
+                &OperationType::Text(TextOperation::Upper),
+                &OperationType::MultAll,
+                &OperationType::Text(TextOperation::Pad),
+                &OperationType::Text(TextOperation::Replace("a".into(), "b".into())),
+                &OperationType::Text(TextOperation::Trim),
+            ]
+        );
+        assert_eq!(res.operations[2].argument.left(), Some(-5));
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_predicate() -> anyhow::Result<()> {
+        let res = parse_command("\"1\" ** 2 where > 10 and < 20 or not in -1..=1 ** 3")?;
+        assert_eq!(
+            res.operations[0].predicate,
+            Some(Predicate::Or(
+                Box::new(Predicate::And(
+                    Box::new(Predicate::Compare(Comparison::Greater, 10.0)),
+                    Box::new(Predicate::Compare(Comparison::Less, 20.0)),
+                )),
+                Box::new(Predicate::Not(Box::new(Predicate::Range {
+                    start: -1.0,
+                    end: 1.0,
+                    inclusive: true,
+                }))),
+            ))
+        );
+        assert_eq!(res.operations[1].predicate, None);
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_bounds() -> anyhow::Result<()> {
+        let res = parse_command("\"1\" clamp -1 2.5 clamp[1] 0 1 min 3 max[-1] 0")?;
+        let types: Vec<_> = res.operations.iter().map(|o| &o.operation_type).collect();
+        assert_eq!(
+            types,
+            [
+                &OperationType::Clamp(None, -1.0),
+                &OperationType::Clamp(Some(1), 0.0),
+                &OperationType::Min(None),
+                &OperationType::Max(Some(-1)),
+            ]
+        );
+        assert_eq!(res.operations[0].argument.right(), Some(2.5));
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_arithmetic() -> anyhow::Result<()> {
+        let res = parse_command("\"1\" ^ 2 ^^ 3 %[1] 4 %% 5 / 6 // 7")?;
+        let types: Vec<_> = res.operations.iter().map(|o| &o.operation_type).collect();
+        assert_eq!(
+            types,
+            [
+                &OperationType::Pow(Some(0)),
+                &OperationType::Pow(None),
+                &OperationType::Mod(Some(1)),
+                &OperationType::Mod(None),
+                &OperationType::IntDiv(Some(0)),
+                &OperationType::IntDiv(None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_list_with_macros() -> anyhow::Result<()> {
+        let res = parse_list(
+            "\"x 1\" @double_label ** 2\nmacro double_label = *[0] 2 @triple\n\
+             macro triple = *** 3 sep \",\"\n\"y 2\" @triple",
+        )?;
+        assert_eq!(res.len(), 2);
+        let first = res[0].as_ref().unwrap();
+        let types: Vec<_> = first.operations.iter().map(|o| &o.operation_type).collect();
+        assert_eq!(types.len(), 3);
+        assert_eq!(types[0], &OperationType::Mult(Some(0)));
+        assert!(matches!(types[1], OperationType::DuplicateWith(_)));
+        assert_eq!(types[2], &OperationType::MultAll);
+        assert_eq!(res[1].as_ref().unwrap().operations.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_macro() -> anyhow::Result<()> {
+        let res = parse_list("macro a = ** 2\n\"x 1\" @a @b")?;
+        match &res[0] {
+            Err(ParseError::UnknownMacro(name, span)) => {
+                assert_eq!(name, "b");
+                assert_eq!(*span, Span { start: 24, end: 26 });
+            }
+            other => return Err(anyhow::anyhow!("wrong result {other:?}")),
+        }
+        assert!(matches!(
+            parse_command("\"x 1\" @a"),
+            Err(ParseError::UnknownMacro(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_macro() -> anyhow::Result<()> {
+        let res = parse_list("macro a = ** 2 @b\nmacro b = @a\n\"x 1\" @a")?;
+        match &res[0] {
+            Err(ParseError::RecursiveMacro(name, span)) => {
+                assert_eq!(name, "a");
+                assert_eq!(*span, Span { start: 28, end: 30 });
+            }
+            other => return Err(anyhow::anyhow!("wrong result {other:?}")),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_functions() -> anyhow::Result<()> {
+        let data = "fn scale(f, n) = ** f @copies\nmacro copies = *** n\n\"3 cups\" @scale(1.5, 2)";
+        let functions = parse_functions(data)?;
+        let scale = &functions["scale"];
+        assert_eq!(scale.params, ["f", "n"]);
+        assert_eq!(scale.operations.len(), 2);
+        assert_eq!(scale.bindings, [(0, 0), (1, 1)]);
+        let res = parse_list(data)?;
+        assert_eq!(res.len(), 1);
+        let command = res[0].as_ref().unwrap();
+        assert_eq!(
+            command.operations[0].operation_type,
+            OperationType::Call("scale".to_string(), vec![1.5, 2.0])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_parameter() -> anyhow::Result<()> {
+        let res = parse_list("fn f(a) = ** b\n\"x 1\" ** a")?;
+        assert_eq!(res.len(), 2);
+        match &res[0] {
+            Err(ParseError::UnknownParameter(name, span)) => {
+                assert_eq!(name, "b");
+                assert_eq!(*span, Span { start: 13, end: 14 });
+            }
+            other => return Err(anyhow::anyhow!("wrong result {other:?}")),
+        }
+        assert!(matches!(&res[1], Err(ParseError::UnknownParameter(name, _)) if name == "a"));
+        Ok(())
+    }
+
+    #[test]
+    fn parsing_list_with_comments() -> anyhow::Result<()> {
+        let res = parse_list("# prices\n\"x 1\" ** 2 # double\ngarbage # note\n")?;
+        assert_eq!(res.len(), 2);
+        assert!(res[0].is_ok());
+        assert!(matches!(&res[1], Err(ParseError::WrongCommand(text)) if text == "garbage "));
+        Ok(())
+    }
+
     #[test]
     fn no_valid_command() -> anyhow::Result<()> {
         let res = parse_command("\"15 packs, 10mg/l *[1]\" ***** 10");
         assert!(res.is_err());
-        return if let Err(ParseError::WrongCommand(_)) = res {
+        if let Err(ParseError::WrongCommand(_)) = res {
             Ok(())
         } else {
             Err(anyhow::anyhow!("wrong error"))
-        };
+        }
     }
 
     #[test]
     fn wrong_command_in_list() -> anyhow::Result<()> {
         let res = parse_list("\"15 packs, 10mg/l *[1]\" ***** 10");
         assert!(res.is_ok());
-        return if let Err(ParseError::WrongCommand(_)) = res.unwrap()[0] {
+        if let Err(ParseError::WrongCommand(_)) = res.unwrap()[0] {
             Ok(())
         } else {
             Err(anyhow::anyhow!("wrong error"))
-        };
+        }
+    }
+
+    #[test]
+    fn backslashes() -> anyhow::Result<()> {
+        let res = parse_command(r#""C:\dir\ 1\\" ** 2"#)?;
+        assert_eq!(res.params[0], ParamsPiece::Str(r"C:\dir\ ".to_string()));
+        assert_eq!(res.params[2], ParamsPiece::Str(r"\".to_string()));
+        assert!(parse_command(r#""C:\" ** 2"#).is_err());
+        Ok(())
     }
 
     #[test]
@@ -85,10 +338,10 @@ mod parsing {
     fn int_too_long() -> anyhow::Result<()> {
         let res = parse_command("\"15 packs, 10mg/l\" *** 10000000000000000000000000000000000000000000000000000000000000");
         assert!(res.is_err());
-        return if let Err(ParseError::ParseIntError(_)) = res {
+        if let Err(ParseError::ParseIntError(_)) = res {
             Ok(())
         } else {
             Err(anyhow::anyhow!("wrong error"))
-        };
+        }
     }
 }
@@ -0,0 +1,178 @@
+use proptest::prelude::*;
+use string_mult::parsing::parse_command;
+use string_mult::printing::*;
+
+mod printing {
+    use super::*;
+
+    #[test]
+    fn canonical_form() -> anyhow::Result<()> {
+        let command = parse_command(
+            "\"15 packs,\t10mg/l\"*2  *[1]\t3 ^^2 %%5 *** 2 sep\", \"step[0]  1 rev pieces ** 2 where not >3 and (<1 or in 5..=7)",
+        )?;
+        assert_eq!(
+            command.to_string(),
+            "\"15 packs,\t10mg/l\" *[0] 2 *[1] 3 ^^ 2 %% 5 *** 2 sep \", \" step[0] 1 rev pieces ** 2 where not > 3 and (< 1 or in 5..=7)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_strings() -> anyhow::Result<()> {
+        let command = parse_command(r#""say \"hi\" 2 C:\dir\\" replace "\"" "\\""#)?;
+        assert_eq!(
+            command.params[0],
+            string_mult::ParamsPiece::Str("say \"hi\" ".to_string())
+        );
+        assert_eq!(
+            print(&command),
+            r#""say \"hi\" 2 C:\\dir\\" replace "\"" "\\""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn options() -> anyhow::Result<()> {
+        let command = parse_command("\"x 1\" * 2 ^ 3 clamp[0] 1 5 @f(1.5, -2)")?;
+        let options = PrintOptions {
+            explicit_indices: false,
+            spaced_arguments: false,
+        };
+        assert_eq!(
+            print_with(&command, &options),
+            "\"x 1\" *2 ^3 clamp[0]1 5 @f(1.5, -2)"
+        );
+        assert_eq!(command.operations[2].to_string(), "clamp[0] 1 5");
+        Ok(())
+    }
+
+    #[test]
+    fn number_before_dot() -> anyhow::Result<()> {
+        let command = parse_command("\"v1.0.5 ١٢\" ** 2")?;
+        assert_eq!(print(&command), "\"v١.٠.٥ ١٢\" ** 2");
+        Ok(())
+    }
+}
+
+fn number() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (-10_000i64..10_000).prop_map(|n| n.to_string()),
+        (0u32..1000, 0u32..1000).prop_map(|(a, b)| format!("-{a}.{b:03}")),
+        (1usize..10, 300usize..320).prop_map(|(a, zeros)| format!("{a}{}", "0".repeat(zeros))),
+        any::<f64>()
+            .prop_filter("finite", |f| f.is_finite())
+            .prop_map(|f| f.to_string()),
+        (0u32..100).prop_map(|n| n
+            .to_string()
+            .chars()
+            .map(|c| char::from_u32('٠' as u32 + c.to_digit(10).unwrap()).unwrap())
+            .collect()),
+    ]
+}
+
+fn text() -> impl Strategy<Value = String> {
+    let chars = prop::sample::select(vec![
+        'a', 'Z', ' ', '\t', '.', '-', ',', '\\', '"', '\u{301}', 'é', '😀', '[', '5',
+    ]);
+    prop::collection::vec(chars, 1..6).prop_map(|chars| {
+        chars
+            .into_iter()
+            .map(|c| match c {
+                '\\' | '"' => format!("\\{c}"),
+                c => c.to_string(),
+            })
+            .collect()
+    })
+}
+
+fn predicate() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        (
+            prop::sample::select(vec![">", "<", ">=", "<=", "==", "!="]),
+            number()
+        )
+            .prop_map(|(op, n)| format!("{op} {n}")),
+        (number(), prop::bool::ANY, number()).prop_map(|(a, inclusive, b)| match inclusive {
+            true => format!("in {a}..={b}"),
+            false => format!("in {a}..{b}"),
+        }),
+    ];
+    leaf.prop_recursive(3, 8, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|p| format!("not ({p})")),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("({a}) and ({b})")),
+            (inner.clone(), inner).prop_map(|(a, b)| format!("{a} or {b}")),
+        ]
+    })
+}
+
+fn duplicate_option() -> impl Strategy<Value = String> {
+    prop_oneof![
+        text().prop_map(|t| format!(" sep \"{t}\"")),
+        (-3isize..3, number()).prop_map(|(i, n)| format!(" step[{i}] {n}")),
+        number().prop_map(|n| format!(" scale {n}")),
+        prop::sample::select(vec![" rev chars", " rev graphemes", " rev pieces"])
+            .prop_map(str::to_string),
+    ]
+}
+
+fn operation() -> impl Strategy<Value = String> {
+    let index = -3isize..3;
+    prop_oneof![
+        (index.clone(), number()).prop_map(|(i, n)| format!("*[{i}] {n}")),
+        number().prop_map(|n| format!("* {n}")),
+        number().prop_map(|n| format!("** {n}")),
+        (number(), predicate()).prop_map(|(n, p)| format!("** {n} where {p}")),
+        (number(), number()).prop_map(|(a, b)| format!("clamp {a} {b}")),
+        (index.clone(), number()).prop_map(|(i, n)| format!("max[{i}] {n}")),
+        number().prop_map(|n| format!("min {n}")),
+        (
+            prop::sample::select(vec!["^", "^^", "%", "%%", "/", "//"]),
+            number()
+        )
+            .prop_map(|(op, n)| format!("{op} {n}")),
+        (index.clone(), number()).prop_map(|(i, n)| format!("%[{i}] {n}")),
+        (-5isize..5, prop::collection::vec(duplicate_option(), 0..3))
+            .prop_map(|(k, options)| format!("*** {k}{}", options.concat())),
+        prop::sample::select(vec!["upper", "lower", "title", "trim"]).prop_map(str::to_string),
+        index.prop_map(|i| format!("pad {i}")),
+        (text(), text()).prop_map(|(a, b)| format!("replace \"{a}\" \"{b}\"")),
+        prop::collection::vec(number(), 0..3).prop_map(|args| format!("@f({})", args.join(", "))),
+    ]
+}
+
+fn command() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![number(), text()];
+    (
+        prop::collection::vec(piece, 0..6),
+        prop::collection::vec(operation(), 1..5),
+    )
+        .prop_map(|(pieces, operations)| {
+            format!("\"{}\" {}", pieces.concat(), operations.join(" "))
+        })
+}
+
+proptest! {
+    #[test]
+    fn round_trip(source in command()) {
+        let command = parse_command(&source);
+        prop_assume!(command.is_ok());
+        let command = command.unwrap();
+        let printed = print(&command);
+        let reparsed = parse_command(&printed);
+        prop_assert!(reparsed.is_ok(), "{} failed to parse", printed);
+        prop_assert_eq!(reparsed.unwrap(), command);
+    }
+
+    #[test]
+    fn round_trip_with_options(source in command(), explicit_indices: bool, spaced_arguments: bool) {
+        let command = parse_command(&source);
+        prop_assume!(command.is_ok());
+        let command = command.unwrap();
+        let options = PrintOptions { explicit_indices, spaced_arguments };
+        let printed = print_with(&command, &options);
+        let reparsed = parse_command(&printed);
+        prop_assert!(reparsed.is_ok(), "{} failed to parse", printed);
+        prop_assert_eq!(reparsed.unwrap(), command);
+    }
+}
@@ -0,0 +1,86 @@
+use string_mult::evaluating::EvalContext;
+use string_mult::reporting::*;
+
+mod reporting {
+    use super::*;
+
+    fn write(input: &str, format: OutputFormat) -> anyhow::Result<String> {
+        let records = records(input, &EvalContext::default())?;
+        let mut output = Vec::new();
+        write_records(&mut output, format, &records)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn records_of_list() -> anyhow::Result<()> {
+        let records = records(
+            "# c\n  \"a 1\" ** 2\n\"a 1\" *[3] 2",
+            &EvalContext::default(),
+        )?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].line, 2);
+        assert_eq!(records[0].command, "\"a 1\" ** 2");
+        assert_eq!((records[0].span.start, records[0].span.end), (6, 16));
+        assert_eq!(records[0].result.as_ref().unwrap(), "a 2");
+        assert_eq!(
+            records[1].result.as_ref().unwrap_err().code(),
+            "index_out_of_range"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn json_lines() -> anyhow::Result<()> {
+        let output = write("\"a\\\"\tb 1\" ** 2\n\"a 1\" % 0", OutputFormat::Jsonl)?;
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"line":1,"command":"\"a\\\"\tb 1\" ** 2","status":"ok","result":"a\"\tb 2","span":{"start":0,"end":14}}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"line":2,"command":"\"a 1\" % 0","status":"error","error":{"code":"zero_modulus","message":"modulus is zero"},"span":{"start":15,"end":24}}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn json_array() -> anyhow::Result<()> {
+        assert_eq!(write("# nothing", OutputFormat::Json)?, "[]\n");
+        let output = write("\"a 1\" ** 2\n\"b 1\" ** 2", OutputFormat::Json)?;
+        assert!(output.starts_with("[\n  {\"line\":1,"));
+        assert!(output.contains("},\n  {\"line\":2,"));
+        assert!(output.ends_with("}\n]\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn separated_values() -> anyhow::Result<()> {
+        let input = "\"a, \\\"b\\\" 1\" ** 2\n\"a\tb 1\" % 0";
+        let csv = write(input, OutputFormat::Csv)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "line,command,status,result,error_code,error_message,span_start,span_end"
+        );
+        assert_eq!(
+            lines[1],
+            r#"1,"""a, \""b\"" 1"" ** 2",ok,"a, ""b"" 2",,,0,17"#
+        );
+        let tsv = write(input, OutputFormat::Tsv)?;
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(
+            lines[2],
+            "2\t\"a\\tb 1\" % 0\terror\t\tzero_modulus\tmodulus is zero\t18\t29"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_names() {
+        for format in OutputFormat::ALL {
+            assert_eq!(OutputFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(OutputFormat::from_name("xml"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use string_mult::evaluating::{evaluate, EvalError};
+
+mod serialization {
+    use super::*;
+
+    #[test]
+    fn error_codes() {
+        let code = |input: &str| evaluate(input).unwrap_err().code();
+        assert_eq!(code("\"a 1\" *[3] 2"), "index_out_of_range");
+        assert_eq!(code("\"a 1\" % 0"), "zero_modulus");
+        assert_eq!(code("\"a 1\" @f()"), "unknown_function");
+        assert_eq!(code("\"a 1\" @m"), "unknown_macro");
+        assert_eq!(EvalError::Unknown.code(), "unknown");
+    }
+
+    #[cfg(feature = "serde")]
+    mod json {
+        use super::*;
+        use serde_json::json;
+        use string_mult::parsing::parse_command;
+        use string_mult::{ParamsPiece, StringMultCommand};
+
+        #[test]
+        fn command_round_trip() -> anyhow::Result<()> {
+            let command = parse_command(concat!(
+                "\"3 cups\" ** 1.5 where > 1 and < 3 *[0] 2 ",
+                "*** -2 sep \", \" step 1 rev chars upper replace \"c\" \"d\""
+            ))?;
+            let json = serde_json::to_string(&command)?;
+            assert_eq!(serde_json::from_str::<StringMultCommand>(&json)?, command);
+            Ok(())
+        }
+
+        #[test]
+        fn argument_schema() -> anyhow::Result<()> {
+            let command = parse_command("\"3 cups\" ** 2 *** 3")?;
+            let value = serde_json::to_value(&command)?;
+            assert_eq!(value["operations"][0]["argument"], json!({ "float": 2.0 }));
+            assert_eq!(value["operations"][1]["argument"], json!({ "int": 3 }));
+            let value = serde_json::to_value(&command.params)?;
+            assert_eq!(
+                value,
+                json!([{ "Num": { "value": 3.0, "integer": true } }, { "Str": " cups" }])
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn bare_numbers() -> anyhow::Result<()> {
+            let pieces: Vec<ParamsPiece> =
+                serde_json::from_value(json!([{ "Num": 3.0 }, { "Num": 1.5 }]))?;
+            assert_eq!(
+                pieces,
+                [
+                    ParamsPiece::Num {
+                        value: 3.0,
+                        integer: true
+                    },
+                    ParamsPiece::Num {
+                        value: 1.5,
+                        integer: false
+                    }
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn errors_as_data() -> anyhow::Result<()> {
+            let result = evaluate("\"a 1\" *[3] 2");
+            assert_eq!(
+                serde_json::to_value(&result)?,
+                json!({ "Err": {
+                    "code": "index_out_of_range",
+                    "message": "index '3' out of range '0..1'"
+                } })
+            );
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use either::Either;
+use string_mult::evaluating::evaluate_command;
+use string_mult::solving::*;
+use string_mult::OperationType;
+
+mod solving {
+    use super::*;
+
+    #[test]
+    fn single_factor() -> anyhow::Result<()> {
+        let command = solve("\"3 cups and 4 spoons\"", "4.5 cups and 6 spoons")?;
+        assert_eq!(command.operations.len(), 1);
+        assert_eq!(command.operations[0].operation_type, OperationType::MultAll);
+        assert_eq!(command.operations[0].argument, Either::Right(1.5));
+        assert_eq!(evaluate_command(&command)?, "4.5 cups and 6 spoons");
+        Ok(())
+    }
+
+    #[test]
+    fn factors_per_index() -> anyhow::Result<()> {
+        let command = solve(
+            "\"3 cups and 4 spoons and 0 forks\"",
+            "6 cups and 4 spoons and 0 forks",
+        )?;
+        assert_eq!(
+            command.to_string(),
+            "\"3 cups and 4 spoons and 0 forks\" *[0] 2"
+        );
+        let command = solve("\"1 a 2 b\"", "3 a 8 b")?;
+        assert_eq!(command.to_string(), "\"1 a 2 b\" *[0] 3 *[1] 4");
+        Ok(())
+    }
+
+    #[test]
+    fn duplication_count() -> anyhow::Result<()> {
+        assert_eq!(
+            solve("\"ab 1\"", "ab 1ab 1ab 1")?.to_string(),
+            "\"ab 1\" *** 3"
+        );
+        assert_eq!(
+            solve("\"ab 1\"", "ab 2ab 2")?.to_string(),
+            "\"ab 1\" ** 2 *** 2"
+        );
+        assert_eq!(solve("\"ab 1\"", "")?.to_string(), "\"ab 1\" *** 0");
+        assert_eq!(solve("\"ab 1\"", "ab 1")?.to_string(), "\"ab 1\" ** 1");
+        Ok(())
+    }
+
+    #[test]
+    fn inconsistent_outputs() {
+        assert!(matches!(
+            solve("\"3 cups\"", "3 cups 4"),
+            Err(SolveError::NumberCount(1, 2))
+        ));
+        assert!(matches!(
+            solve("\"3 cups\"", "6 mugs"),
+            Err(SolveError::TextMismatch(from, to)) if from == " cups" && to == " mugs"
+        ));
+        assert!(matches!(
+            solve("\"0 cups\"", "2 cups"),
+            Err(SolveError::ZeroNumber(0, n)) if n == 2.0
+        ));
+        assert!(matches!(
+            solve("\"3 cups\"", "3.000000001 cups"),
+            Err(SolveError::Inexact(output)) if output == "3 cups"
+        ));
+        assert!(matches!(
+            solve("3 cups", "3 cups"),
+            Err(SolveError::ParseError(_))
+        ));
+    }
+}
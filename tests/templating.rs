@@ -0,0 +1,58 @@
+mod templating {
+    use string_mult::evaluating::EvalError;
+    use string_mult::templating::*;
+    use string_mult::Span;
+
+    #[test]
+    fn replaces_commands() -> anyhow::Result<()> {
+        let res =
+            render("# Order\n\nBuy {{ \"12 packs\" ** 2 }} and {{\"1 box\" *** 2 sep \", \"}}.\n")?;
+        assert_eq!(res, "# Order\n\nBuy 24 packs and 1 box, 1 box.\n");
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_text_untouched() -> anyhow::Result<()> {
+        let text = "<p>No { commands } \"here\" 12 ** 2</p>";
+        assert_eq!(render(text)?, text);
+        Ok(())
+    }
+
+    #[test]
+    fn close_mark_inside_quotes() -> anyhow::Result<()> {
+        assert_eq!(render("{{ \"a }} 2\" ** 3 }}}")?, "a }} 6}");
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_quote_inside_quotes() -> anyhow::Result<()> {
+        assert_eq!(render("{{ \"say \\\"}}\\\" 2\" ** 2 }}")?, "say \"}}\" 4");
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_open_mark() -> anyhow::Result<()> {
+        assert_eq!(render("{{{{ x }} and {{ \"1\" ** 2 }}")?, "{{ x }} and 2");
+        Ok(())
+    }
+
+    #[test]
+    fn unclosed_placeholder() -> anyhow::Result<()> {
+        match render("text {{ \"1\" ** 2 }} and {{ \"1\" ** 2") {
+            Err(TemplateError::Unclosed(span)) => assert_eq!(span, Span { start: 24, end: 26 }),
+            other => return Err(anyhow::anyhow!("wrong result {other:?}")),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn failed_command() -> anyhow::Result<()> {
+        match render("a {{ \"x\" *[0] 2 }}") {
+            Err(TemplateError::Command(EvalError::IndexOutOfRange(0, 0), span)) => {
+                assert_eq!(span, Span { start: 2, end: 18 })
+            }
+            other => return Err(anyhow::anyhow!("wrong result {other:?}")),
+        }
+        Ok(())
+    }
+}